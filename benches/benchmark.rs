@@ -4,8 +4,9 @@ use criterion::{criterion_group, criterion_main, Criterion};
 use dashmap::DashMap;
 // TODO: Re-export this under lunatic_runtime
 use lunatic_process::{
-    runtimes::wasmtime::{default_config, WasmtimeRuntime},
+    runtimes::wasmtime::{default_config, Preemption, WasmtimeRuntime},
     state::ProcessState,
+    table::ProcessTable,
 };
 use lunatic_runtime::{spawn_wasm, DefaultProcessConfig, DefaultProcessState};
 
@@ -14,7 +15,7 @@ fn criterion_benchmark(c: &mut Criterion) {
 
     let config = Arc::new(DefaultProcessConfig::default());
     let wasmtime_config = default_config();
-    let runtime = WasmtimeRuntime::new(&wasmtime_config).unwrap();
+    let runtime = WasmtimeRuntime::new(&wasmtime_config, Preemption::Fuel).unwrap();
 
     let raw_module = wat::parse_file("./wat/hello.wat").unwrap();
     let module = runtime
@@ -24,9 +25,15 @@ fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("spawn process", |b| {
         b.to_async(&rt).iter(|| async {
             let registry = Arc::new(DashMap::new());
-            let state =
-                DefaultProcessState::new(runtime.clone(), module.clone(), config.clone(), registry)
-                    .unwrap();
+            let process_table = ProcessTable::new();
+            let state = DefaultProcessState::new(
+                runtime.clone(),
+                module.clone(),
+                config.clone(),
+                registry,
+                process_table,
+            )
+            .unwrap();
             spawn_wasm(
                 runtime.clone(),
                 module.clone(),
@@ -34,6 +41,7 @@ fn criterion_benchmark(c: &mut Criterion) {
                 "hello",
                 Vec::new(),
                 None,
+                None,
             )
             .await
             .unwrap()
@@ -2,23 +2,29 @@ use std::{
     convert::TryInto,
     future::Future,
     io::{Read, Write},
-    time::Duration,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 use anyhow::Result;
 use lunatic_common_api::{get_memory, IntoTrap};
+use lunatic_error_api::ErrorCtx;
 use lunatic_networking_api::NetworkingCtx;
 use lunatic_process_api::ProcessCtx;
 use wasmtime::{Caller, Linker, Trap};
 
 use lunatic_process::{
-    message::{DataMessage, Message},
+    config::ProcessConfig,
+    info::ProcessStatus,
+    mailbox::Selector,
+    message::{DataMessage, Expiry, Message, MessageKind, SharedBuffer},
+    runtimes::wasmtime::WasmtimeCompiledModule,
     state::ProcessState,
-    Signal,
+    ExitReason, Process, Signal, WasmProcess,
 };
 
 // Register the mailbox APIs to the linker
-pub fn register<T: ProcessState + ProcessCtx<T> + NetworkingCtx + Send + 'static>(
+pub fn register<T: ProcessState + ProcessCtx<T> + NetworkingCtx + ErrorCtx + Send + 'static>(
     linker: &mut Linker<T>,
 ) -> Result<()> {
     linker.func_wrap("lunatic::message", "create_data", create_data)?;
@@ -26,30 +32,134 @@ pub fn register<T: ProcessState + ProcessCtx<T> + NetworkingCtx + Send + 'static
     linker.func_wrap("lunatic::message", "read_data", read_data)?;
     linker.func_wrap("lunatic::message", "seek_data", seek_data)?;
     linker.func_wrap("lunatic::message", "get_tag", get_tag)?;
+    linker.func_wrap("lunatic::message", "set_priority", set_priority)?;
+    linker.func_wrap("lunatic::message", "get_priority", get_priority)?;
+    linker.func_wrap("lunatic::message", "set_ttl", set_ttl)?;
+    linker.func_wrap("lunatic::message", "get_monitor_ref", get_monitor_ref)?;
+    linker.func_wrap(
+        "lunatic::message",
+        "get_process_down_id",
+        get_process_down_id,
+    )?;
+    linker.func_wrap("lunatic::message", "exit_reason", exit_reason)?;
+    linker.func_wrap("lunatic::message", "get_kill_reason", get_kill_reason)?;
+    linker.func_wrap(
+        "lunatic::message",
+        "get_dead_letter_recipient",
+        get_dead_letter_recipient,
+    )?;
+    linker.func_wrap("lunatic::message", "unwrap_dead_letter", unwrap_dead_letter)?;
     linker.func_wrap("lunatic::message", "data_size", data_size)?;
     linker.func_wrap("lunatic::message", "push_process", push_process)?;
     linker.func_wrap("lunatic::message", "take_process", take_process)?;
     linker.func_wrap("lunatic::message", "push_tcp_stream", push_tcp_stream)?;
     linker.func_wrap("lunatic::message", "take_tcp_stream", take_tcp_stream)?;
     linker.func_wrap("lunatic::message", "send", send)?;
+    linker.func_wrap("lunatic::message", "send_to_group", send_to_group)?;
     linker.func_wrap2_async(
         "lunatic::message",
         "send_receive_skip_search",
         send_receive_skip_search,
     )?;
+    linker.func_wrap("lunatic::message", "make_ref", make_ref)?;
+    linker.func_wrap("lunatic::message", "abandon_ref", abandon_ref)?;
+    linker.func_wrap("lunatic::message", "dropped_replies", dropped_replies)?;
+    linker.func_wrap2_async("lunatic::message", "call", call)?;
     linker.func_wrap3_async("lunatic::message", "receive", receive)?;
+    linker.func_wrap4_async("lunatic::message", "receive_select", receive_select)?;
+    linker.func_wrap("lunatic::message", "peek", peek)?;
+    linker.func_wrap4_async("lunatic::message", "receive_many", receive_many)?;
+    linker.func_wrap("lunatic::message", "next_in_batch", next_in_batch)?;
     linker.func_wrap("lunatic::message", "push_udp_socket", push_udp_socket)?;
     linker.func_wrap("lunatic::message", "take_udp_socket", take_udp_socket)?;
+    linker.func_wrap("lunatic::message", "push_tcp_listener", push_tcp_listener)?;
+    linker.func_wrap("lunatic::message", "take_tcp_listener", take_tcp_listener)?;
+    linker.func_wrap("lunatic::message", "push_module", push_module)?;
+    linker.func_wrap("lunatic::message", "take_module", take_module)?;
+    linker.func_wrap("lunatic::message", "push_config", push_config)?;
+    linker.func_wrap("lunatic::message", "take_config", take_config)?;
+    linker.func_wrap(
+        "lunatic::message",
+        "create_shared_buffer",
+        create_shared_buffer,
+    )?;
+    linker.func_wrap("lunatic::message", "drop_shared_buffer", drop_shared_buffer)?;
+    linker.func_wrap("lunatic::message", "shared_buffer_size", shared_buffer_size)?;
+    linker.func_wrap("lunatic::message", "read_shared_buffer", read_shared_buffer)?;
+    linker.func_wrap("lunatic::message", "push_shared_buffer", push_shared_buffer)?;
+    linker.func_wrap("lunatic::message", "take_shared_buffer", take_shared_buffer)?;
+
+    linker.func_wrap("lunatic::message", "create_data_handle", create_data_handle)?;
+    linker.func_wrap("lunatic::message", "drop_handle", drop_handle)?;
+    linker.func_wrap(
+        "lunatic::message",
+        "scratch_area_to_handle",
+        scratch_area_to_handle,
+    )?;
+    linker.func_wrap(
+        "lunatic::message",
+        "handle_to_scratch_area",
+        handle_to_scratch_area,
+    )?;
+    linker.func_wrap("lunatic::message", "write_data_handle", write_data_handle)?;
+    linker.func_wrap("lunatic::message", "read_data_handle", read_data_handle)?;
+    linker.func_wrap("lunatic::message", "seek_data_handle", seek_data_handle)?;
+    linker.func_wrap("lunatic::message", "get_tag_handle", get_tag_handle)?;
+    linker.func_wrap("lunatic::message", "data_size_handle", data_size_handle)?;
+    linker.func_wrap(
+        "lunatic::message",
+        "push_process_handle",
+        push_process_handle,
+    )?;
+    linker.func_wrap(
+        "lunatic::message",
+        "take_process_handle",
+        take_process_handle,
+    )?;
+    linker.func_wrap(
+        "lunatic::message",
+        "push_tcp_stream_handle",
+        push_tcp_stream_handle,
+    )?;
+    linker.func_wrap(
+        "lunatic::message",
+        "take_tcp_stream_handle",
+        take_tcp_stream_handle,
+    )?;
+    linker.func_wrap(
+        "lunatic::message",
+        "push_udp_socket_handle",
+        push_udp_socket_handle,
+    )?;
+    linker.func_wrap(
+        "lunatic::message",
+        "take_udp_socket_handle",
+        take_udp_socket_handle,
+    )?;
+    linker.func_wrap("lunatic::message", "send_handle", send_handle)?;
 
     Ok(())
 }
 
-// There are two kinds of messages a lunatic process can receive:
+// There are five kinds of messages a lunatic process can receive:
 //
 // 1. **Data message** that contains a buffer of raw `u8` data and host side resources.
 // 2. **LinkDied message**, representing a `LinkDied` signal that was turned into a message. The
 //    process can control if when a link dies the process should die too, or just receive a
 //    `LinkDied` message notifying it about the link's death.
+// 3. **ProcessDown message**, notifying the process that a monitored process finished. Monitors
+//    never kill the receiving process. The monitor reference and the ID of the finished process
+//    can be read with `get_monitor_ref` and `get_process_down_id`.
+// 4. **Shutdown message**, representing a `Shutdown` signal that was turned into a message. The
+//    process should finish as soon as possible, otherwise it's killed after the shutdown timeout.
+// 5. **DeadLetter message**, wrapping a message that couldn't be delivered, because the receiving
+//    process already finished. It's only received by the process registered as the runtime's
+//    dead-letter sink. The intended recipient can be read with `get_dead_letter_recipient` and the
+//    original message can be put into the scratch area with `unwrap_dead_letter`.
+//
+// Both, `LinkDied` and `ProcessDown` messages, carry the reason of the process' death. It can be
+// read with `exit_reason`. If the process was killed with a reason, it can be read with
+// `get_kill_reason`.
 //
 // All messages have a `tag` allowing for selective receives. If there are already messages in the
 // receiving queue, they will be first searched for a specific tag and the first match returned.
@@ -98,6 +208,16 @@ pub fn register<T: ProcessState + ProcessCtx<T> + NetworkingCtx + Send + 'static
 // called `create_data` again before sending the message, the current buffer and resources
 // would be dropped.
 //
+// To work on multiple messages at once, messages can be kept as handles. The functions with a
+// `_handle` suffix work the same way as the ones without it, but take the ID of a message handle
+// instead of using the scratch area. `create_data_handle` creates a new data message handle and
+// `scratch_area_to_handle` turns a received message into one. This allows building a reply while
+// still holding on to the request.
+//
+// Large payloads that are sent to many processes can be put into a shared buffer with
+// `create_shared_buffer`. Shared buffers are immutable and reference counted, attaching one to a
+// message with `push_shared_buffer` doesn't copy the data, no matter how many messages hold it.
+//
 // On the receiving side, first the `receive(tag)` function must be called. If `tag` has a value
 // different from 0, the function will only return messages that have the specific `tag`. Once
 // a message is received, we can read from its buffer or extract resources from it.
@@ -119,26 +239,33 @@ pub fn register<T: ProcessState + ProcessCtx<T> + NetworkingCtx + Send + 'static
 //
 // Arguments:
 // * tag - An identifier that can be used for selective receives. If value is 0, no tag is used.
-// * buffer_capacity - A hint to the message to pre-allocate a large enough buffer for writes.
+// * buffer_capacity - A hint to the message to pre-allocate a large enough buffer for writes. It's
+//                     capped at the maximum message size and the memory left to the process.
+//
+// Traps:
+// * If the process doesn't export a memory.
 fn create_data<T: ProcessState + ProcessCtx<T>>(
     mut caller: Caller<T>,
     tag: i64,
     buffer_capacity: u64,
-) {
+) -> Result<(), Trap> {
     let tag = match tag {
         0 => None,
         tag => Some(tag),
     };
-    let message = DataMessage::new(tag, buffer_capacity as usize);
+    let message = new_data_message(&mut caller, tag, buffer_capacity)?;
     caller
         .data_mut()
         .message_scratch_area()
         .replace(Message::Data(message));
+    Ok(())
 }
 
 // Writes some data into the message buffer and returns how much data is written in bytes.
 //
 // Traps:
+// * If the message would exceed the maximum message size.
+// * If the message would exceed the memory limit of the process.
 // * If any memory outside the guest heap space is referenced.
 // * If it's called without a data message being inside of the scratch area.
 fn write_data<T: ProcessState + ProcessCtx<T>>(
@@ -146,26 +273,13 @@ fn write_data<T: ProcessState + ProcessCtx<T>>(
     data_ptr: u32,
     data_len: u32,
 ) -> Result<u32, Trap> {
-    let memory = get_memory(&mut caller)?;
-    let mut message = caller
-        .data_mut()
-        .message_scratch_area()
-        .take()
-        .or_trap("lunatic::message::write_data")?;
-    let buffer = memory
-        .data(&caller)
-        .get(data_ptr as usize..(data_ptr as usize + data_len as usize))
-        .or_trap("lunatic::message::write_data")?;
-    let bytes = match &mut message {
-        Message::Data(data) => data.write(buffer).or_trap("lunatic::message::write_data")?,
-        Message::LinkDied(_) => {
-            return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
-        }
-    };
-    // Put message back after writing to it.
-    caller.data_mut().message_scratch_area().replace(message);
-
-    Ok(bytes as u32)
+    write_to(
+        &mut caller,
+        Target::ScratchArea,
+        data_ptr,
+        data_len,
+        "lunatic::message::write_data",
+    )
 }
 
 // Reads some data from the message buffer and returns how much data is read in bytes.
@@ -178,26 +292,13 @@ fn read_data<T: ProcessState + ProcessCtx<T>>(
     data_ptr: u32,
     data_len: u32,
 ) -> Result<u32, Trap> {
-    let memory = get_memory(&mut caller)?;
-    let mut message = caller
-        .data_mut()
-        .message_scratch_area()
-        .take()
-        .or_trap("lunatic::message::read_data")?;
-    let buffer = memory
-        .data_mut(&mut caller)
-        .get_mut(data_ptr as usize..(data_ptr as usize + data_len as usize))
-        .or_trap("lunatic::message::read_data")?;
-    let bytes = match &mut message {
-        Message::Data(data) => data.read(buffer).or_trap("lunatic::message::read_data")?,
-        Message::LinkDied(_) => {
-            return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
-        }
-    };
-    // Put message back after reading from it.
-    caller.data_mut().message_scratch_area().replace(message);
-
-    Ok(bytes as u32)
+    read_from(
+        &mut caller,
+        Target::ScratchArea,
+        data_ptr,
+        data_len,
+        "lunatic::message::read_data",
+    )
 }
 
 // Moves reading head of the internal message buffer. It's useful if you wish to read the a bit
@@ -210,17 +311,12 @@ fn seek_data<T: ProcessState + ProcessCtx<T>>(
     mut caller: Caller<T>,
     index: u64,
 ) -> Result<(), Trap> {
-    let mut message = caller
-        .data_mut()
-        .message_scratch_area()
-        .as_mut()
-        .or_trap("lunatic::message::seek_data")?;
-    match &mut message {
-        Message::Data(data) => data.seek(index as usize),
-        Message::LinkDied(_) => {
-            return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
-        }
-    };
+    data_message(
+        caller.data_mut(),
+        Target::ScratchArea,
+        "lunatic::message::seek_data",
+    )?
+    .seek(index as usize);
     Ok(())
 }
 
@@ -240,24 +336,286 @@ fn get_tag<T: ProcessState + ProcessCtx<T>>(mut caller: Caller<T>) -> Result<i64
     }
 }
 
-// Returns the size in bytes of the message buffer.
+// Sets the priority of the data message in the scratch area. Messages with a higher priority are
+// received before the ones with a lower priority. The default priority is 0.
 //
 // Traps:
 // * If it's called without a data message being inside of the scratch area.
-fn data_size<T: ProcessState + ProcessCtx<T>>(mut caller: Caller<T>) -> Result<u64, Trap> {
+fn set_priority<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    priority: u32,
+) -> Result<(), Trap> {
+    data_message(
+        caller.data_mut(),
+        Target::ScratchArea,
+        "lunatic::message::set_priority",
+    )?
+    .priority = priority;
+    Ok(())
+}
+
+// Returns the priority of the message in the scratch area. Shutdown requests have the highest
+// priority, all other messages that are not data messages have a priority of 0.
+//
+// Traps:
+// * If it's called without a message being inside of the scratch area.
+fn get_priority<T: ProcessState + ProcessCtx<T>>(mut caller: Caller<T>) -> Result<u32, Trap> {
+    let message = caller
+        .data_mut()
+        .message_scratch_area()
+        .as_ref()
+        .or_trap("lunatic::message::get_priority")?;
+    Ok(message.priority())
+}
+
+// Sets the time to live of the data message in the scratch area to **ttl_ms** milliseconds,
+// counted from the time this function is called. If the message is not received in time, it's
+// discarded by the receiving process. A time to live too large to represent removes the expiry.
+//
+// If **notify_tag** is a value different from 0, an empty data message with this tag is sent back
+// to the current process once the message is discarded.
+//
+// Traps:
+// * If it's called without a data message being inside of the scratch area.
+fn set_ttl<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    ttl_ms: u64,
+    notify_tag: i64,
+) -> Result<(), Trap> {
+    // A time to live too large to represent never expires
+    let deadline = Instant::now().checked_add(Duration::from_millis(ttl_ms));
+    let notify = match notify_tag {
+        0 => None,
+        tag => {
+            let state = caller.data();
+            let this_process: Arc<dyn Process> = Arc::new(WasmProcess::new(
+                state.id(),
+                state.signal_mailbox().0.clone(),
+                state.process_info().clone(),
+                state.process_table().dead_letters().clone(),
+            ));
+            Some((this_process, tag))
+        }
+    };
+    data_message(
+        caller.data_mut(),
+        Target::ScratchArea,
+        "lunatic::message::set_ttl",
+    )?
+    .expiry = deadline.map(|deadline| Expiry { deadline, notify });
+    Ok(())
+}
+
+// Returns the monitor reference of the `ProcessDown` message in the scratch area.
+//
+// Traps:
+// * If it's called without a `ProcessDown` message being inside of the scratch area.
+fn get_monitor_ref<T: ProcessState + ProcessCtx<T>>(mut caller: Caller<T>) -> Result<u64, Trap> {
     let message = caller
         .data_mut()
         .message_scratch_area()
         .as_ref()
-        .or_trap("lunatic::message::data_size")?;
-    let bytes = match message {
-        Message::Data(data) => data.size(),
-        Message::LinkDied(_) => {
-            return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
+        .or_trap("lunatic::message::get_monitor_ref")?;
+    match message {
+        Message::ProcessDown { monitor_ref, .. } => Ok(*monitor_ref),
+        _ => Err(Trap::new("Expected `Message::ProcessDown` in scratch area")),
+    }
+}
+
+// Writes the UUID of the finished process from the `ProcessDown` message in the scratch area to
+// **u128_ptr**.
+//
+// Traps:
+// * If it's called without a `ProcessDown` message being inside of the scratch area.
+// * If any memory outside the guest heap space is referenced.
+fn get_process_down_id<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    u128_ptr: u32,
+) -> Result<(), Trap> {
+    let process_id = match caller
+        .data_mut()
+        .message_scratch_area()
+        .as_ref()
+        .or_trap("lunatic::message::get_process_down_id")?
+    {
+        Message::ProcessDown { process_id, .. } => process_id.as_u128(),
+        _ => return Err(Trap::new("Expected `Message::ProcessDown` in scratch area")),
+    };
+    let memory = get_memory(&mut caller)?;
+    memory
+        .write(&mut caller, u128_ptr as usize, &process_id.to_le_bytes())
+        .or_trap("lunatic::message::get_process_down_id")?;
+    Ok(())
+}
+
+// Returns the exit reason of the `LinkDied` or `ProcessDown` message in the scratch area.
+//
+// If the process didn't finish normally, an error describing the reason is created and its ID is
+// written to **error_id_ptr**. The error can be turned into a string with the
+// `lunatic::error::to_string` function.
+//
+// Returns:
+// * 0 if the process finished normally.
+// * 1 if the process trapped.
+// * 2 if the process was killed.
+// * 3 if the process used up all of its fuel.
+// * 4 if the process reached its memory limit.
+// * 5 if the process failed to spawn.
+// * 6 if a host function panicked.
+// * 7 if the process' mailbox overflowed.
+// * 8 if the process reached its execution time limit.
+// * 9 if the process reached its CPU time limit.
+// * 10 if the process already finished when it was linked or monitored.
+// * 11 if the process didn't shut down in time.
+//
+// Traps:
+// * If it's called without a `LinkDied` or `ProcessDown` message being inside of the scratch
+//   area.
+// * If any memory outside the guest heap space is referenced.
+fn exit_reason<T: ProcessState + ProcessCtx<T> + ErrorCtx>(
+    mut caller: Caller<T>,
+    error_id_ptr: u32,
+) -> Result<u32, Trap> {
+    let reason = match caller
+        .data_mut()
+        .message_scratch_area()
+        .as_ref()
+        .or_trap("lunatic::message::exit_reason")?
+    {
+        Message::LinkDied(_, reason) => reason.clone(),
+        Message::ProcessDown { reason, .. } => reason.clone(),
+        other => {
+            return Err(Trap::new(format!(
+                "Unexpected `Message::{:?}` in scratch area",
+                other.kind()
+            )))
+        }
+    };
+    let code = match reason {
+        ExitReason::Normal => return Ok(0),
+        ExitReason::Trapped(_) => 1,
+        ExitReason::Killed { .. } => 2,
+        ExitReason::FuelExhausted => 3,
+        ExitReason::MemoryLimit => 4,
+        ExitReason::SpawnError(_) => 5,
+        ExitReason::Panicked(_) => 6,
+        ExitReason::MailboxOverflow => 7,
+        ExitReason::ExecutionTimeLimit => 8,
+        ExitReason::CpuTimeLimit => 9,
+        ExitReason::NoProcess => 10,
+        ExitReason::ShutdownTimeout => 11,
+    };
+    let error_id = caller
+        .data_mut()
+        .error_resources_mut()
+        .add(anyhow::Error::msg(reason.to_string()));
+    let memory = get_memory(&mut caller)?;
+    memory
+        .write(&mut caller, error_id_ptr as usize, &error_id.to_le_bytes())
+        .or_trap("lunatic::message::exit_reason")?;
+    Ok(code)
+}
+
+// Returns the reason passed to `lunatic::process::kill` if the process from the `LinkDied` or
+// `ProcessDown` message in the scratch area was killed with one.
+//
+// Returns:
+// * 0 if the process wasn't killed or was killed without a reason.
+// * The kill reason otherwise.
+//
+// Traps:
+// * If it's called without a `LinkDied` or `ProcessDown` message being inside of the scratch
+//   area.
+fn get_kill_reason<T: ProcessState + ProcessCtx<T>>(mut caller: Caller<T>) -> Result<i64, Trap> {
+    let reason = match caller
+        .data_mut()
+        .message_scratch_area()
+        .as_ref()
+        .or_trap("lunatic::message::get_kill_reason")?
+    {
+        Message::LinkDied(_, reason) => reason,
+        Message::ProcessDown { reason, .. } => reason,
+        other => {
+            return Err(Trap::new(format!(
+                "Unexpected `Message::{:?}` in scratch area",
+                other.kind()
+            )))
+        }
+    };
+    match reason {
+        ExitReason::Killed {
+            reason: Some(reason),
+            ..
+        } => Ok(*reason),
+        _ => Ok(0),
+    }
+}
+
+// Writes the UUID of the process the `DeadLetter` message in the scratch area was sent to to
+// **u128_ptr**.
+//
+// Traps:
+// * If it's called without a `DeadLetter` message being inside of the scratch area.
+// * If any memory outside the guest heap space is referenced.
+fn get_dead_letter_recipient<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    u128_ptr: u32,
+) -> Result<(), Trap> {
+    let recipient = match caller
+        .data_mut()
+        .message_scratch_area()
+        .as_ref()
+        .or_trap("lunatic::message::get_dead_letter_recipient")?
+    {
+        Message::DeadLetter { recipient, .. } => recipient.as_u128(),
+        _ => return Err(Trap::new("Expected `Message::DeadLetter` in scratch area")),
+    };
+    let memory = get_memory(&mut caller)?;
+    memory
+        .write(&mut caller, u128_ptr as usize, &recipient.to_le_bytes())
+        .or_trap("lunatic::message::get_dead_letter_recipient")?;
+    Ok(())
+}
+
+// Replaces the `DeadLetter` message in the scratch area with the undelivered message inside of it,
+// so that it can be read like a received message.
+//
+// Returns:
+// * 0 if it's a data message.
+// * 1 if it's a signal turned into a message.
+// * 2 if it's a notification about a monitored process finishing.
+// * 3 if it's a request to shut down.
+//
+// Traps:
+// * If it's called without a `DeadLetter` message being inside of the scratch area.
+fn unwrap_dead_letter<T: ProcessState + ProcessCtx<T>>(mut caller: Caller<T>) -> Result<u32, Trap> {
+    let scratch_area = caller.data_mut().message_scratch_area();
+    let message = match scratch_area
+        .take()
+        .or_trap("lunatic::message::unwrap_dead_letter")?
+    {
+        Message::DeadLetter { message, .. } => *message,
+        other => {
+            scratch_area.replace(other);
+            return Err(Trap::new("Expected `Message::DeadLetter` in scratch area"));
         }
     };
+    let result = message_kind_code(message.kind());
+    scratch_area.replace(message);
+    Ok(result)
+}
 
-    Ok(bytes as u64)
+// Returns the size in bytes of the message buffer.
+//
+// Traps:
+// * If it's called without a data message being inside of the scratch area.
+fn data_size<T: ProcessState + ProcessCtx<T>>(mut caller: Caller<T>) -> Result<u64, Trap> {
+    let data = data_message(
+        caller.data_mut(),
+        Target::ScratchArea,
+        "lunatic::message::data_size",
+    )?;
+    Ok(data.size() as u64)
 }
 
 // Adds a process resource to the message that is currently in the scratch area and returns
@@ -272,23 +630,12 @@ fn push_process<T: ProcessState + ProcessCtx<T>>(
     mut caller: Caller<T>,
     process_id: u64,
 ) -> Result<u64, Trap> {
-    let process = caller
-        .data_mut()
-        .process_resources_mut()
-        .remove(process_id)
-        .or_trap("lunatic::message::push_process")?;
-    let message = caller
-        .data_mut()
-        .message_scratch_area()
-        .as_mut()
-        .or_trap("lunatic::message::push_process")?;
-    let index = match message {
-        Message::Data(data) => data.add_process(process) as u64,
-        Message::LinkDied(_) => {
-            return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
-        }
-    };
-    Ok(index)
+    add_process(
+        caller.data_mut(),
+        Target::ScratchArea,
+        process_id,
+        "lunatic::message::push_process",
+    )
 }
 
 // Takes the process handle from the message that is currently in the scratch area by index, puts
@@ -301,20 +648,12 @@ fn take_process<T: ProcessState + ProcessCtx<T>>(
     mut caller: Caller<T>,
     index: u64,
 ) -> Result<u64, Trap> {
-    let message = caller
-        .data_mut()
-        .message_scratch_area()
-        .as_mut()
-        .or_trap("lunatic::message::take_process")?;
-    let process = match message {
-        Message::Data(data) => data
-            .take_process(index as usize)
-            .or_trap("lunatic::message::take_process")?,
-        Message::LinkDied(_) => {
-            return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
-        }
-    };
-    Ok(caller.data_mut().process_resources_mut().add(process))
+    take_process_from(
+        caller.data_mut(),
+        Target::ScratchArea,
+        index,
+        "lunatic::message::take_process",
+    )
 }
 
 // Adds a tcp stream resource to the message that is currently in the scratch area and returns
@@ -327,23 +666,12 @@ fn push_tcp_stream<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
     mut caller: Caller<T>,
     stream_id: u64,
 ) -> Result<u64, Trap> {
-    let stream = caller
-        .data_mut()
-        .tcp_stream_resources_mut()
-        .remove(stream_id)
-        .or_trap("lunatic::message::push_tcp_stream")?;
-    let message = caller
-        .data_mut()
-        .message_scratch_area()
-        .as_mut()
-        .or_trap("lunatic::message::push_tcp_stream")?;
-    let index = match message {
-        Message::Data(data) => data.add_tcp_stream(stream) as u64,
-        Message::LinkDied(_) => {
-            return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
-        }
-    };
-    Ok(index)
+    add_tcp_stream(
+        caller.data_mut(),
+        Target::ScratchArea,
+        stream_id,
+        "lunatic::message::push_tcp_stream",
+    )
 }
 
 // Takes the tcp stream from the message that is currently in the scratch area by index, puts
@@ -356,45 +684,108 @@ fn take_tcp_stream<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
     mut caller: Caller<T>,
     index: u64,
 ) -> Result<u64, Trap> {
-    let message = caller
-        .data_mut()
-        .message_scratch_area()
-        .as_mut()
-        .or_trap("lunatic::message::take_tcp_stream")?;
-    let tcp_stream = match message {
-        Message::Data(data) => data
-            .take_tcp_stream(index as usize)
-            .or_trap("lunatic::message::take_tcp_stream")?,
-        Message::LinkDied(_) => {
-            return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
-        }
-    };
-    Ok(caller.data_mut().tcp_stream_resources_mut().add(tcp_stream))
+    take_tcp_stream_from(
+        caller.data_mut(),
+        Target::ScratchArea,
+        index,
+        "lunatic::message::take_tcp_stream",
+    )
 }
 
 // Sends the message to a process.
 //
 // There are no guarantees that the message will be received.
 //
+// If the receiving process uses the back-pressure overflow policy and asks senders to back off, the
+// message is not sent and stays in the scratch area. It can be sent again later or dropped by
+// creating the next message.
+//
+// Returns:
+// * 0 if the message was sent.
+// * 1 if the receiving process asked senders to back off.
+//
 // Traps:
 // * If the process ID doesn't exist.
 // * If it's called before creating the next message.
 fn send<T: ProcessState + ProcessCtx<T>>(
     mut caller: Caller<T>,
     process_id: u64,
-) -> Result<(), Trap> {
+) -> Result<u32, Trap> {
+    let process = caller
+        .data()
+        .process_resources()
+        .get(process_id)
+        .or_trap("lunatic::message::send")?
+        .clone();
+    if process.back_pressure() {
+        return Ok(1);
+    }
     let message = caller
         .data_mut()
         .message_scratch_area()
         .take()
         .or_trap("lunatic::message::send")?;
-    let process = caller
-        .data_mut()
-        .process_resources_mut()
-        .get(process_id)
-        .or_trap("lunatic::message::send")?;
     process.send(Signal::Message(message));
-    Ok(())
+    Ok(0)
+}
+
+// Sends the message to all members of the process group with the name at **name_str_ptr**.
+//
+// Each member receives its own copy of the message. Process handles, UDP sockets, TCP listeners and
+// shared buffers are shared between the copies, other resources can't be sent to a group. Members
+// that asked senders to back off are skipped. If no member receives the message, it stays in the
+// scratch area and its resources can still be taken back.
+//
+// Returns:
+// * The number of processes the message was sent to.
+//
+// Traps:
+// * If it's called before creating the next message.
+// * If the message contains resources that can't be shared.
+// * If the name is not a valid utf8 string.
+// * If any memory outside the guest heap space is referenced.
+fn send_to_group<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+) -> Result<u64, Trap> {
+    let memory = get_memory(&mut caller)?;
+    let (memory_slice, state) = memory.data_and_store_mut(&mut caller);
+    let name = memory_slice
+        .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
+        .or_trap("lunatic::message::send_to_group")?;
+    let name = std::str::from_utf8(name).or_trap("lunatic::message::send_to_group")?;
+    // Check the message first, so that it's not sent to some members before trapping.
+    match state
+        .message_scratch_area()
+        .as_ref()
+        .or_trap("lunatic::message::send_to_group")?
+    {
+        Message::Data(data) if data.is_shareable() => {}
+        Message::Data(_) => {
+            return Err(Trap::new(
+                "lunatic::message::send_to_group: resources can't be shared",
+            ))
+        }
+        _ => return Err(Trap::new("Only data messages can be sent to a group")),
+    }
+    let mut members = state.process_table().groups().members(name);
+    members.retain(|member| !member.back_pressure());
+    let last = match members.pop() {
+        Some(last) => last,
+        None => return Ok(0),
+    };
+    let message = match state.message_scratch_area().take() {
+        Some(Message::Data(data)) => data,
+        _ => unreachable!("checked above"),
+    };
+    for member in &members {
+        let copy = message.try_clone().expect("checked above");
+        member.send(Signal::Message(Message::Data(copy)));
+    }
+    // The last member gets the original message
+    last.send(Signal::Message(Message::Data(message)));
+    Ok(members.len() as u64 + 1)
 }
 
 // Sends the message to a process and waits for a reply, but doesn't look through existing
@@ -406,7 +797,8 @@ fn send<T: ProcessState + ProcessCtx<T>>(
 // miss out on the incoming message before `receive` is called.
 //
 // If timeout is specified (value different from 0), the function will return on timeout
-// expiration with value 9027.
+// expiration with value 9027. If the message is tagged with a reference, a reply arriving after the
+// timeout can be dropped with `lunatic::message::abandon_ref`.
 //
 // Returns:
 // * 0    if message arrived.
@@ -426,8 +818,9 @@ fn send_receive_skip_search<T: ProcessState + ProcessCtx<T> + Send>(
             .message_scratch_area()
             .take()
             .or_trap("lunatic::message::send_receive_skip_search")?;
+        let tag = message.tag();
         let mut _tags = [0; 1];
-        let tags = if let Some(tag) = message.tag() {
+        let tags = if let Some(tag) = tag {
             _tags = [tag];
             Some(&_tags[..])
         } else {
@@ -439,10 +832,15 @@ fn send_receive_skip_search<T: ProcessState + ProcessCtx<T> + Send>(
             .get(process_id)
             .or_trap("lunatic::message::send_receive_skip_search")?;
         process.send(Signal::Message(message));
-        if let Some(message) = tokio::select! {
+        let info = caller.data().process_info().clone();
+        info.set_fuel_consumed(caller.fuel_consumed().unwrap_or(0));
+        info.set_status(ProcessStatus::Waiting);
+        let message = tokio::select! {
             _ = async_std::task::sleep(Duration::from_millis(timeout as u64)), if timeout != 0 => None,
             message = caller.data_mut().mailbox().pop_skip_search(tags) => Some(message)
-        } {
+        };
+        info.set_status(ProcessStatus::Running);
+        if let Some(message) = message {
             // Put the message into the scratch area
             caller.data_mut().message_scratch_area().replace(message);
             Ok(0)
@@ -452,67 +850,432 @@ fn send_receive_skip_search<T: ProcessState + ProcessCtx<T> + Send>(
     })
 }
 
-// Takes the next message out of the queue or blocks until the next message is received if queue
-// is empty.
+// Returns a new reference that is unique inside the runtime.
 //
-// If **tag_len** is a value greater than 0 it will block until a message is received matching any
-// of the supplied tags. **tag_ptr** points to an array containing i64 value encoded as little
-// endian values.
+// References are negative values and can be used as tags to match replies to requests. They are
+// only guaranteed to be different from other references, a guest can still pick the same value as
+// a tag. To avoid collisions, guests should only use positive values as their own tags.
+fn make_ref() -> i64 {
+    lunatic_process::new_ref()
+}
+
+// Stops waiting on replies tagged with **reference**. The next data message with this tag is
+// dropped, or right away if it already arrived, and counted by `lunatic::message::dropped_replies`.
+//
+// Only the most recently abandoned references are remembered, a reply arriving much later could
+// still show up in the mailbox.
+//
+// Traps:
+// * If **reference** wasn't created with `lunatic::message::make_ref`.
+fn abandon_ref<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    reference: i64,
+) -> Result<(), Trap> {
+    if !lunatic_process::is_ref(reference) {
+        return Err(Trap::new(
+            "lunatic::message::abandon_ref: Value is not a reference",
+        ));
+    }
+    caller.data_mut().mailbox().abandon(reference);
+    Ok(())
+}
+
+// Returns the number of late replies the current process dropped, because it stopped waiting on
+// them after a `lunatic::message::call` timed out or their reference was abandoned with
+// `lunatic::message::abandon_ref`.
+fn dropped_replies<T: ProcessState + ProcessCtx<T>>(mut caller: Caller<T>) -> u64 {
+    caller.data_mut().mailbox().dropped_replies()
+}
+
+// Sends the message in the scratch area to a process and waits for the reply.
+//
+// The message is tagged with a new reference (see `lunatic::message::make_ref`) and only a reply
+// carrying the same tag is received. The receiving process should reply with the tag of the
+// request. If the call times out, a reply arriving later is dropped and never shows up in the
+// mailbox.
+//
+// The receiving process is monitored while waiting on the reply. If it finishes (or doesn't exist
+// anymore) before replying, the call returns right away with the `ProcessDown` message in the
+// scratch area.
 //
 // If timeout is specified (value different from 0), the function will return on timeout
 // expiration with value 9027.
 //
-// Once the message is received, functions like `lunatic::message::read_data()` can be used to
-// extract data out of it.
-//
 // Returns:
-// * 0    if it's a data message.
-// * 1    if it's a signal turned into a message.
+// * 0    if the reply arrived and was put into the scratch area.
+// * 1    if the receiving process asked senders to back off. The message stays in the scratch area.
+// * 2    if the receiving process finished before replying.
 // * 9027 if call timed out.
 //
 // Traps:
-// * If **tag_ptr + (ciovec_array_len * 8) is outside the memory
-fn receive<T: ProcessState + ProcessCtx<T> + Send>(
+// * If the process ID doesn't exist.
+// * If it's called without a data message being inside of the scratch area.
+fn call<T: ProcessState + ProcessCtx<T> + Send>(
     mut caller: Caller<T>,
-    tag_ptr: u32,
-    tag_len: u32,
+    process_id: u64,
     timeout: u32,
 ) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_> {
     Box::new(async move {
-        let tags = if tag_len > 0 {
-            let memory = get_memory(&mut caller)?;
-            let buffer = memory
-                .data(&caller)
-                .get(tag_ptr as usize..(tag_ptr + tag_len * 8) as usize)
-                .or_trap("lunatic::message::receive")?;
-
-            // Gether all tags
-            let tags: Vec<i64> = buffer
-                .chunks_exact(8)
-                .map(|chunk| i64::from_le_bytes(chunk.try_into().expect("works")))
-                .collect();
-            Some(tags)
-        } else {
-            None
+        let process = caller
+            .data()
+            .process_resources()
+            .get(process_id)
+            .or_trap("lunatic::message::call")?
+            .clone();
+        if process.back_pressure() {
+            return Ok(1);
+        }
+        let mut message = caller
+            .data_mut()
+            .message_scratch_area()
+            .take()
+            .or_trap("lunatic::message::call")?;
+        let reference = lunatic_process::new_ref();
+        data_message_mut(&mut message)?.tag = Some(reference);
+        // Watch the receiving process, so that the call doesn't wait forever if it dies.
+        let state = caller.data();
+        let this_process = WasmProcess::new(
+            state.id(),
+            state.signal_mailbox().0.clone(),
+            state.process_info().clone(),
+            state.process_table().dead_letters().clone(),
+        );
+        let monitor_ref = lunatic_process::new_monitor_ref();
+        process.send(Signal::Monitor(monitor_ref, Arc::new(this_process)));
+        // The reference and monitor are new, so the reply can't be in the mailbox yet.
+        process.send(Signal::Message(message));
+        let info = caller.data().process_info().clone();
+        info.set_fuel_consumed(caller.fuel_consumed().unwrap_or(0));
+        info.set_status(ProcessStatus::Waiting);
+        let mailbox = caller.data_mut().mailbox().clone();
+        let selector = Selector::Reply {
+            tag: reference,
+            monitor_ref,
         };
-
-        if let Some(message) = tokio::select! {
+        let message = tokio::select! {
             _ = async_std::task::sleep(Duration::from_millis(timeout as u64)), if timeout != 0 => None,
-            message = caller.data_mut().mailbox().pop(tags.as_deref()) => Some(message)
-        } {
-            let result = match message {
-                Message::Data(_) => 0,
-                Message::LinkDied(_) => 1,
-            };
-            // Put the message into the scratch area
-            caller.data_mut().message_scratch_area().replace(message);
-            Ok(result)
+            message = mailbox.pop_skip_search_select(selector) => Some(message)
+        };
+        info.set_status(ProcessStatus::Running);
+        if let Some(Message::ProcessDown { .. }) = message {
+            // The request could have been forwarded to a process that still replies.
+            mailbox.abandon(reference);
         } else {
-            Ok(9027)
+            // The monitor is only needed during the call. If the process finishes before the
+            // `Demonitor` signal arrives, the notification is dropped.
+            process.send(Signal::Demonitor(monitor_ref));
+            mailbox.abandon_monitor(monitor_ref);
+        }
+        match message {
+            Some(message) => {
+                let result = match message {
+                    Message::ProcessDown { .. } => 2,
+                    _ => 0,
+                };
+                caller.data_mut().message_scratch_area().replace(message);
+                Ok(result)
+            }
+            None => {
+                mailbox.abandon(reference);
+                Ok(9027)
+            }
+        }
+    })
+}
+
+// Takes the next message out of the queue or blocks until the next message is received if queue
+// is empty.
+//
+// If **tag_len** is a value greater than 0 it will block until a message is received matching any
+// of the supplied tags. **tag_ptr** points to an array containing i64 value encoded as little
+// endian values.
+//
+// If timeout is specified (value different from 0), the function will return on timeout
+// expiration with value 9027. Replies that are not needed anymore after the timeout can be dropped
+// with `lunatic::message::abandon_ref`.
+//
+// Once the message is received, functions like `lunatic::message::read_data()` can be used to
+// extract data out of it.
+//
+// Returns:
+// * 0    if it's a data message.
+// * 1    if it's a signal turned into a message.
+// * 2    if it's a notification about a monitored process finishing.
+// * 3    if it's a request to shut down.
+// * 4    if it's an undelivered message forwarded to the dead-letter sink.
+// * 9027 if call timed out.
+//
+// Traps:
+// * If **tag_ptr + (ciovec_array_len * 8) is outside the memory
+fn receive<T: ProcessState + ProcessCtx<T> + Send>(
+    mut caller: Caller<T>,
+    tag_ptr: u32,
+    tag_len: u32,
+    timeout: u32,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_> {
+    Box::new(async move {
+        let tags = if tag_len > 0 {
+            read_i64_array(&mut caller, tag_ptr, tag_len, "lunatic::message::receive")?
+        } else {
+            Vec::new()
+        };
+        let selector = if tags.is_empty() {
+            Selector::Any
+        } else {
+            Selector::Tags(tags)
+        };
+        Ok(receive_matching(&mut caller, selector, timeout).await)
+    })
+}
+
+// Takes the first message matching the selector out of the queue or blocks until a matching
+// message is received.
+//
+// The selector is described by the **selector** kind and an array of **args_len** i64 arguments
+// encoded as little endian values at **args_ptr**:
+// * 0 - Any message, no arguments.
+// * 1 - Messages with one of the tags in the arguments.
+// * 2 - Messages with a tag inside the range, the arguments are the first and last tag.
+// * 3 - All messages, except the ones with one of the tags in the arguments.
+// * 4 - Messages of a kind, the argument is the code of it (as returned by this function).
+// * 5 - The `ProcessDown` message of a monitor, the argument is the monitor reference.
+//
+// If timeout is specified (value different from 0), the function will return on timeout
+// expiration with value 9027.
+//
+// Returns:
+// * 0    if it's a data message.
+// * 1    if it's a signal turned into a message.
+// * 2    if it's a notification about a monitored process finishing.
+// * 3    if it's a request to shut down.
+// * 4    if it's an undelivered message forwarded to the dead-letter sink.
+// * 9027 if call timed out.
+//
+// Traps:
+// * If **args_ptr + (args_len * 8) is outside the memory.
+// * If the selector is not valid.
+fn receive_select<T: ProcessState + ProcessCtx<T> + Send>(
+    mut caller: Caller<T>,
+    selector: u32,
+    args_ptr: u32,
+    args_len: u32,
+    timeout: u32,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_> {
+    Box::new(async move {
+        let selector = read_selector(
+            &mut caller,
+            selector,
+            args_ptr,
+            args_len,
+            "lunatic::message::receive_select",
+        )?;
+        Ok(receive_matching(&mut caller, selector, timeout).await)
+    })
+}
+
+// Looks at the first message matching the selector without taking it out of the queue. The
+// selector is described the same way as for `lunatic::message::receive_select`.
+//
+// If a message matches, its tag (or 0 if it doesn't have one) is written to **tag_ptr**. This
+// function never blocks.
+//
+// Returns:
+// * 0    if it's a data message.
+// * 1    if it's a signal turned into a message.
+// * 2    if it's a notification about a monitored process finishing.
+// * 3    if it's a request to shut down.
+// * 4    if it's an undelivered message forwarded to the dead-letter sink.
+// * 9027 if no message matches.
+//
+// Traps:
+// * If **args_ptr + (args_len * 8) is outside the memory.
+// * If the selector is not valid.
+// * If **tag_ptr** is outside the memory.
+fn peek<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    selector: u32,
+    args_ptr: u32,
+    args_len: u32,
+    tag_ptr: u32,
+) -> Result<u32, Trap> {
+    let selector = read_selector(
+        &mut caller,
+        selector,
+        args_ptr,
+        args_len,
+        "lunatic::message::peek",
+    )?;
+    let peeked = caller
+        .data_mut()
+        .mailbox()
+        .peek(&selector, |message| (message.kind(), message.tag()));
+    match peeked {
+        Some((kind, tag)) => {
+            let memory = get_memory(&mut caller)?;
+            memory
+                .write(
+                    &mut caller,
+                    tag_ptr as usize,
+                    &tag.unwrap_or(0).to_le_bytes(),
+                )
+                .or_trap("lunatic::message::peek")?;
+            Ok(message_kind_code(kind))
+        }
+        None => Ok(9027),
+    }
+}
+
+// Takes up to **max** messages out of the queue and puts them into the batch. Blocks until at least
+// one message is received if queue is empty, but doesn't wait on more. Messages in the batch still
+// count against the mailbox capacity until they are moved into the scratch area.
+//
+// The tags and timeout work the same way as for `lunatic::message::receive`. Messages that are left
+// in the batch from a previous call are kept in front of the new ones.
+//
+// Once the messages are received, `lunatic::message::next_in_batch` moves them one by one into the
+// scratch area.
+//
+// Returns:
+// * The number of received messages.
+// * 0 if call timed out.
+//
+// Traps:
+// * If **max** is 0.
+// * If **tag_ptr + (tag_len * 8)** is outside the memory.
+fn receive_many<T: ProcessState + ProcessCtx<T> + Send>(
+    mut caller: Caller<T>,
+    max: u32,
+    tag_ptr: u32,
+    tag_len: u32,
+    timeout: u32,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_> {
+    Box::new(async move {
+        if max == 0 {
+            return Err(Trap::new(
+                "lunatic::message::receive_many: Can't receive 0 messages",
+            ));
         }
+        let selector = if tag_len > 0 {
+            let tags = read_i64_array(
+                &mut caller,
+                tag_ptr,
+                tag_len,
+                "lunatic::message::receive_many",
+            )?;
+            Selector::Tags(tags)
+        } else {
+            Selector::Any
+        };
+
+        let info = caller.data().process_info().clone();
+        info.set_fuel_consumed(caller.fuel_consumed().unwrap_or(0));
+        info.set_status(ProcessStatus::Waiting);
+        let mailbox = caller.data_mut().mailbox().clone();
+        let received = tokio::select! {
+            _ = async_std::task::sleep(Duration::from_millis(timeout as u64)), if timeout != 0 => 0,
+            received = mailbox.pop_into_batch(max as usize, selector) => received
+        };
+        info.set_status(ProcessStatus::Running);
+        Ok(received as u32)
     })
 }
 
+// Moves the next message of the batch into the scratch area.
+//
+// Returns:
+// * 0    if it's a data message.
+// * 1    if it's a signal turned into a message.
+// * 2    if it's a notification about a monitored process finishing.
+// * 3    if it's a request to shut down.
+// * 4    if it's an undelivered message forwarded to the dead-letter sink.
+// * 9027 if the batch is empty.
+fn next_in_batch<T: ProcessState + ProcessCtx<T>>(mut caller: Caller<T>) -> u32 {
+    match caller.data_mut().mailbox().next_in_batch() {
+        Some(message) => {
+            let result = message_kind_code(message.kind());
+            caller.data_mut().message_scratch_area().replace(message);
+            result
+        }
+        None => 9027,
+    }
+}
+
+// Waits on the next message matching the selector and puts it into the scratch area.
+async fn receive_matching<T: ProcessState + ProcessCtx<T> + Send>(
+    caller: &mut Caller<'_, T>,
+    selector: Selector,
+    timeout: u32,
+) -> u32 {
+    let info = caller.data().process_info().clone();
+    info.set_fuel_consumed(caller.fuel_consumed().unwrap_or(0));
+    info.set_status(ProcessStatus::Waiting);
+    let message = tokio::select! {
+        _ = async_std::task::sleep(Duration::from_millis(timeout as u64)), if timeout != 0 => None,
+        message = caller.data_mut().mailbox().pop_select(selector) => Some(message)
+    };
+    info.set_status(ProcessStatus::Running);
+    if let Some(message) = message {
+        let result = message_kind_code(message.kind());
+        // Put the message into the scratch area
+        caller.data_mut().message_scratch_area().replace(message);
+        result
+    } else {
+        9027
+    }
+}
+
+fn message_kind_code(kind: MessageKind) -> u32 {
+    match kind {
+        MessageKind::Data => 0,
+        MessageKind::LinkDied => 1,
+        MessageKind::ProcessDown => 2,
+        MessageKind::Shutdown => 3,
+        MessageKind::DeadLetter => 4,
+    }
+}
+
+fn read_selector<T: ProcessState + ProcessCtx<T>>(
+    caller: &mut Caller<T>,
+    selector: u32,
+    args_ptr: u32,
+    args_len: u32,
+    fn_name: &str,
+) -> Result<Selector, Trap> {
+    let args = read_i64_array(caller, args_ptr, args_len, fn_name)?;
+    let selector = match (selector, args.as_slice()) {
+        (0, _) => Selector::Any,
+        (1, _) => Selector::Tags(args),
+        (2, [min, max]) => Selector::TagRange(*min, *max),
+        (3, _) => Selector::ExceptTags(args),
+        (4, [0]) => Selector::Kind(MessageKind::Data),
+        (4, [1]) => Selector::Kind(MessageKind::LinkDied),
+        (4, [2]) => Selector::Kind(MessageKind::ProcessDown),
+        (4, [3]) => Selector::Kind(MessageKind::Shutdown),
+        (4, [4]) => Selector::Kind(MessageKind::DeadLetter),
+        (5, [monitor_ref]) => Selector::Monitor(*monitor_ref as u64),
+        _ => return Err(Trap::new(format!("{}: Invalid selector", fn_name))),
+    };
+    Ok(selector)
+}
+
+fn read_i64_array<T: ProcessState + ProcessCtx<T>>(
+    caller: &mut Caller<T>,
+    ptr: u32,
+    len: u32,
+    fn_name: &str,
+) -> Result<Vec<i64>, Trap> {
+    let memory = get_memory(caller)?;
+    let buffer = memory
+        .data(&caller)
+        .get(ptr as usize..(ptr as usize + len as usize * 8))
+        .or_trap(fn_name)?;
+    let values = buffer
+        .chunks_exact(8)
+        .map(|chunk| i64::from_le_bytes(chunk.try_into().expect("works")))
+        .collect();
+    Ok(values)
+}
+
 // Adds a udp socket resource to the message that is currently in the scratch area and returns
 // the new location of it. This will remove the socket from the current process' resources.
 //
@@ -523,22 +1286,12 @@ fn push_udp_socket<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
     mut caller: Caller<T>,
     socket_id: u64,
 ) -> Result<u64, Trap> {
-    let data = caller.data_mut();
-    let socket = data
-        .udp_resources_mut()
-        .remove(socket_id)
-        .or_trap("lunatic::message::push_udp_socket")?;
-    let message = data
-        .message_scratch_area()
-        .as_mut()
-        .or_trap("lunatic::message::push_udp_socket")?;
-    let index = match message {
-        Message::Data(data) => data.add_udp_socket(socket) as u64,
-        Message::LinkDied(_) => {
-            return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
-        }
-    };
-    Ok(index)
+    add_udp_socket(
+        caller.data_mut(),
+        Target::ScratchArea,
+        socket_id,
+        "lunatic::message::push_udp_socket",
+    )
 }
 
 // Takes the udp socket from the message that is currently in the scratch area by index, puts
@@ -550,19 +1303,813 @@ fn push_udp_socket<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
 fn take_udp_socket<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
     mut caller: Caller<T>,
     index: u64,
+) -> Result<u64, Trap> {
+    take_udp_socket_from(
+        caller.data_mut(),
+        Target::ScratchArea,
+        index,
+        "lunatic::message::take_udp_socket",
+    )
+}
+
+// Adds a tcp listener resource to the message that is currently in the scratch area and returns
+// the new location of it. The listener stays in the current process' resources and is shared with
+// the receiver, so that the same listener can be handed to multiple acceptors.
+//
+// Traps:
+// * If TCP listener ID doesn't exist
+// * If no data message is in the scratch area.
+fn push_tcp_listener<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
+    mut caller: Caller<T>,
+    listener_id: u64,
+) -> Result<u64, Trap> {
+    let state = caller.data_mut();
+    let tcp_listener = state
+        .tcp_listener_resources()
+        .get(listener_id)
+        .cloned()
+        .or_trap("lunatic::message::push_tcp_listener")?;
+    let data = data_message(
+        state,
+        Target::ScratchArea,
+        "lunatic::message::push_tcp_listener",
+    )?;
+    Ok(data.add_tcp_listener(tcp_listener) as u64)
+}
+
+// Takes the tcp listener from the message that is currently in the scratch area by index, puts
+// it into the process' resources and returns the resource ID.
+//
+// Traps:
+// * If index ID doesn't exist or matches the wrong resource (not a tcp listener).
+// * If no data message is in the scratch area.
+fn take_tcp_listener<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
+    mut caller: Caller<T>,
+    index: u64,
+) -> Result<u64, Trap> {
+    let state = caller.data_mut();
+    let tcp_listener = data_message(
+        state,
+        Target::ScratchArea,
+        "lunatic::message::take_tcp_listener",
+    )?
+    .take_tcp_listener(index as usize)
+    .or_trap("lunatic::message::take_tcp_listener")?;
+    Ok(state.tcp_listener_resources_mut().add(tcp_listener))
+}
+
+// Adds a module resource to the message that is currently in the scratch area and returns the
+// new location of it. Modules can't be changed after compilation, so the module stays in the
+// current process' resources and the receiver gets a handle to the same compiled module.
+//
+// Traps:
+// * If module ID doesn't exist
+// * If no data message is in the scratch area.
+fn push_module<T: ProcessState + ProcessCtx<T> + Send + 'static>(
+    mut caller: Caller<T>,
+    module_id: u64,
+) -> Result<u64, Trap> {
+    let state = caller.data_mut();
+    // Check the message first, so that the resource isn't lost if there is none.
+    data_message(state, Target::ScratchArea, "lunatic::message::push_module")?;
+    let module = state
+        .module_resources()
+        .get(module_id)
+        .cloned()
+        .or_trap("lunatic::message::push_module")?;
+    let data = data_message(state, Target::ScratchArea, "lunatic::message::push_module")?;
+    Ok(data.add_module(module) as u64)
+}
+
+// Takes the module from the message that is currently in the scratch area by index, puts it
+// into the process' resources and returns the resource ID.
+//
+// Traps:
+// * If index ID doesn't exist or matches the wrong resource (not a module).
+// * If no data message is in the scratch area.
+fn take_module<T: ProcessState + ProcessCtx<T> + Send + 'static>(
+    mut caller: Caller<T>,
+    index: u64,
+) -> Result<u64, Trap> {
+    let state = caller.data_mut();
+    let module = data_message(state, Target::ScratchArea, "lunatic::message::take_module")?
+        .take_module::<WasmtimeCompiledModule<T>>(index as usize)
+        .or_trap("lunatic::message::take_module")?;
+    Ok(state.module_resources_mut().add(module))
+}
+
+// Adds a config resource to the message that is currently in the scratch area and returns the
+// new location of it. This will remove the config from the current process' resources.
+//
+// Traps:
+// * If config ID doesn't exist
+// * If no data message is in the scratch area.
+fn push_config<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    config_id: u64,
+) -> Result<u64, Trap> {
+    let state = caller.data_mut();
+    // Check the message first, so that the resource isn't lost if there is none.
+    data_message(state, Target::ScratchArea, "lunatic::message::push_config")?;
+    let config = state
+        .config_resources_mut()
+        .remove(config_id)
+        .or_trap("lunatic::message::push_config")?;
+    let data = data_message(state, Target::ScratchArea, "lunatic::message::push_config")?;
+    Ok(data.add_config(config) as u64)
+}
+
+// Takes the config from the message that is currently in the scratch area by index, puts it
+// into the process' resources and returns the resource ID.
+//
+// Traps:
+// * If index ID doesn't exist or matches the wrong resource (not a config).
+// * If no data message is in the scratch area.
+fn take_config<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    index: u64,
+) -> Result<u64, Trap> {
+    let state = caller.data_mut();
+    let config = data_message(state, Target::ScratchArea, "lunatic::message::take_config")?
+        .take_config::<T::Config>(index as usize)
+        .or_trap("lunatic::message::take_config")?;
+    Ok(state.config_resources_mut().add(config))
+}
+
+// Creates an immutable shared buffer from the guest memory at **data_ptr** and returns the ID
+// of it.
+//
+// The data is copied once. After that the buffer can be attached to any number of messages with
+// `lunatic::message::push_shared_buffer` without copying it again.
+//
+// Traps:
+// * If the buffer is bigger than the maximum message size.
+// * If the buffer would exceed the memory limit of the process.
+// * If any memory outside the guest heap space is referenced.
+fn create_shared_buffer<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    data_ptr: u32,
+    data_len: u32,
+) -> Result<u64, Trap> {
+    check_message_size(
+        &mut caller,
+        0,
+        data_len as usize,
+        "lunatic::message::create_shared_buffer",
+    )?;
+    let memory = get_memory(&mut caller)?;
+    let (memory_slice, state) = memory.data_and_store_mut(&mut caller);
+    let data = memory_slice
+        .get(data_ptr as usize..(data_ptr as usize + data_len as usize))
+        .or_trap("lunatic::message::create_shared_buffer")?;
+    let mut shared_buffer = SharedBuffer::new(Arc::from(data));
+    shared_buffer.charge_to(state.mailbox().memory());
+    // The buffer stays counted against this process, because it was holding the message.
+    Ok(state.shared_buffer_resources_mut().add(shared_buffer))
+}
+
+// Drops the shared buffer. The data is freed once no message references it anymore.
+//
+// Traps:
+// * If the shared buffer ID doesn't exist.
+fn drop_shared_buffer<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    buffer_id: u64,
+) -> Result<(), Trap> {
+    caller
+        .data_mut()
+        .shared_buffer_resources_mut()
+        .remove(buffer_id)
+        .or_trap("lunatic::message::drop_shared_buffer")?;
+    Ok(())
+}
+
+// Returns the size in bytes of the shared buffer.
+//
+// Traps:
+// * If the shared buffer ID doesn't exist.
+fn shared_buffer_size<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    buffer_id: u64,
+) -> Result<u64, Trap> {
+    let size = caller
+        .data()
+        .shared_buffer_resources()
+        .get(buffer_id)
+        .or_trap("lunatic::message::shared_buffer_size")?
+        .data()
+        .len();
+    Ok(size as u64)
+}
+
+// Reads data starting at **offset** from the shared buffer into the guest memory at **data_ptr**
+// and returns how much data is read in bytes.
+//
+// Traps:
+// * If the shared buffer ID doesn't exist.
+// * If any memory outside the guest heap space is referenced.
+fn read_shared_buffer<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    buffer_id: u64,
+    offset: u64,
+    data_ptr: u32,
+    data_len: u32,
+) -> Result<u32, Trap> {
+    let memory = get_memory(&mut caller)?;
+    let (memory_slice, state) = memory.data_and_store_mut(&mut caller);
+    let shared = state
+        .shared_buffer_resources()
+        .get(buffer_id)
+        .or_trap("lunatic::message::read_shared_buffer")?;
+    let shared = shared.data().get(offset as usize..).unwrap_or_default();
+    let bytes = shared.len().min(data_len as usize);
+    memory_slice
+        .get_mut(data_ptr as usize..(data_ptr as usize + bytes))
+        .or_trap("lunatic::message::read_shared_buffer")?
+        .copy_from_slice(&shared[..bytes]);
+    Ok(bytes as u32)
+}
+
+// Attaches the shared buffer to the message that is currently in the scratch area and returns
+// the new location of it. The buffer is immutable, so it also stays in the current process'
+// resources. Until the message is sent, the buffer is counted twice against the memory limit.
+//
+// Traps:
+// * If the shared buffer ID doesn't exist.
+// * If no data message is in the scratch area.
+fn push_shared_buffer<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    buffer_id: u64,
+) -> Result<u64, Trap> {
+    let state = caller.data_mut();
+    // Check the message first, so that the resource isn't lost if there is none.
+    data_message(
+        state,
+        Target::ScratchArea,
+        "lunatic::message::push_shared_buffer",
+    )?;
+    let shared_buffer = state
+        .shared_buffer_resources()
+        .get(buffer_id)
+        .cloned()
+        .or_trap("lunatic::message::push_shared_buffer")?;
+    let data = data_message(
+        state,
+        Target::ScratchArea,
+        "lunatic::message::push_shared_buffer",
+    )?;
+    Ok(data.add_shared_buffer(shared_buffer) as u64)
+}
+
+// Takes the shared buffer from the message that is currently in the scratch area by index, puts
+// it into the process' resources and returns the resource ID.
+//
+// Traps:
+// * If index ID doesn't exist or matches the wrong resource (not a shared buffer).
+// * If no data message is in the scratch area.
+fn take_shared_buffer<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    index: u64,
+) -> Result<u64, Trap> {
+    let state = caller.data_mut();
+    let shared_buffer = data_message(
+        state,
+        Target::ScratchArea,
+        "lunatic::message::take_shared_buffer",
+    )?
+    .take_shared_buffer(index as usize)
+    .or_trap("lunatic::message::take_shared_buffer")?;
+    // The buffer stays counted against this process, because it was holding the message.
+    Ok(state.shared_buffer_resources_mut().add(shared_buffer))
+}
+
+// Creates a new data message and returns the ID of the handle to it.
+//
+// The message can be modified with the `*_handle` functions. Once `lunatic::message::send_handle`
+// is called it will be sent to another process.
+//
+// Arguments:
+// * tag - An identifier that can be used for selective receives. If value is 0, no tag is used.
+// * buffer_capacity - A hint to the message to pre-allocate a large enough buffer for writes.
+fn create_data_handle<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    tag: i64,
+    buffer_capacity: u64,
+) -> Result<u64, Trap> {
+    let tag = match tag {
+        0 => None,
+        tag => Some(tag),
+    };
+    let message = new_data_message(&mut caller, tag, buffer_capacity)?;
+    Ok(caller
+        .data_mut()
+        .message_resources_mut()
+        .add(Message::Data(message)))
+}
+
+// Drops the message handle.
+//
+// Traps:
+// * If the message ID doesn't exist.
+fn drop_handle<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    message_id: u64,
+) -> Result<(), Trap> {
+    caller
+        .data_mut()
+        .message_resources_mut()
+        .remove(message_id)
+        .or_trap("lunatic::message::drop_handle")?;
+    Ok(())
+}
+
+// Moves the message out of the scratch area and returns the ID of the handle to it.
+//
+// Traps:
+// * If it's called without a message being inside of the scratch area.
+fn scratch_area_to_handle<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
 ) -> Result<u64, Trap> {
     let message = caller
         .data_mut()
         .message_scratch_area()
-        .as_mut()
-        .or_trap("lunatic::message::take_udp_socket")?;
-    let udp_socket = match message {
-        Message::Data(data) => data
-            .take_udp_socket(index as usize)
-            .or_trap("lunatic::message::take_udp_socket")?,
-        Message::LinkDied(_) => {
-            return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
+        .take()
+        .or_trap("lunatic::message::scratch_area_to_handle")?;
+    Ok(caller.data_mut().message_resources_mut().add(message))
+}
+
+// Moves the message out of the handle into the scratch area, so that functions working on the
+// scratch area can be used with it. The handle is consumed and a message that was in the scratch
+// area before is dropped.
+//
+// Traps:
+// * If the message ID doesn't exist.
+fn handle_to_scratch_area<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    message_id: u64,
+) -> Result<(), Trap> {
+    let message = caller
+        .data_mut()
+        .message_resources_mut()
+        .remove(message_id)
+        .or_trap("lunatic::message::handle_to_scratch_area")?;
+    caller.data_mut().message_scratch_area().replace(message);
+    Ok(())
+}
+
+// Writes some data into the buffer of the message handle and returns how much data is written in
+// bytes.
+//
+// Traps:
+// * If any memory outside the guest heap space is referenced.
+// * If the message ID doesn't exist or isn't a data message.
+fn write_data_handle<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    message_id: u64,
+    data_ptr: u32,
+    data_len: u32,
+) -> Result<u32, Trap> {
+    write_to(
+        &mut caller,
+        Target::Handle(message_id),
+        data_ptr,
+        data_len,
+        "lunatic::message::write_data_handle",
+    )
+}
+
+// Reads some data from the buffer of the message handle and returns how much data is read in
+// bytes.
+//
+// Traps:
+// * If any memory outside the guest heap space is referenced.
+// * If the message ID doesn't exist or isn't a data message.
+fn read_data_handle<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    message_id: u64,
+    data_ptr: u32,
+    data_len: u32,
+) -> Result<u32, Trap> {
+    read_from(
+        &mut caller,
+        Target::Handle(message_id),
+        data_ptr,
+        data_len,
+        "lunatic::message::read_data_handle",
+    )
+}
+
+// Moves reading head of the buffer of the message handle.
+//
+// Traps:
+// * If the message ID doesn't exist or isn't a data message.
+fn seek_data_handle<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    message_id: u64,
+    index: u64,
+) -> Result<(), Trap> {
+    data_message(
+        caller.data_mut(),
+        Target::Handle(message_id),
+        "lunatic::message::seek_data_handle",
+    )?
+    .seek(index as usize);
+    Ok(())
+}
+
+// Returns the tag of the message handle or 0 if no tag was set.
+//
+// Traps:
+// * If the message ID doesn't exist.
+fn get_tag_handle<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    message_id: u64,
+) -> Result<i64, Trap> {
+    let message = caller
+        .data()
+        .message_resources()
+        .get(message_id)
+        .or_trap("lunatic::message::get_tag_handle")?;
+    Ok(message.tag().unwrap_or(0))
+}
+
+// Returns the size in bytes of the buffer of the message handle.
+//
+// Traps:
+// * If the message ID doesn't exist or isn't a data message.
+fn data_size_handle<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    message_id: u64,
+) -> Result<u64, Trap> {
+    let data = data_message(
+        caller.data_mut(),
+        Target::Handle(message_id),
+        "lunatic::message::data_size_handle",
+    )?;
+    Ok(data.size() as u64)
+}
+
+// Adds a process resource to the message handle and returns the location in the array the process
+// was added to.
+//
+// This will remove the process handle from the current process' resources.
+//
+// Traps:
+// * If process ID doesn't exist
+// * If the message ID doesn't exist or isn't a data message.
+fn push_process_handle<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    message_id: u64,
+    process_id: u64,
+) -> Result<u64, Trap> {
+    add_process(
+        caller.data_mut(),
+        Target::Handle(message_id),
+        process_id,
+        "lunatic::message::push_process_handle",
+    )
+}
+
+// Takes the process handle from the message handle by index, puts it into the process' resources
+// and returns the resource ID.
+//
+// Traps:
+// * If index ID doesn't exist or matches the wrong resource (not process).
+// * If the message ID doesn't exist or isn't a data message.
+fn take_process_handle<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    message_id: u64,
+    index: u64,
+) -> Result<u64, Trap> {
+    take_process_from(
+        caller.data_mut(),
+        Target::Handle(message_id),
+        index,
+        "lunatic::message::take_process_handle",
+    )
+}
+
+// Adds a tcp stream resource to the message handle and returns the new location of it. This will
+// remove the tcp stream from the current process' resources.
+//
+// Traps:
+// * If TCP stream ID doesn't exist
+// * If the message ID doesn't exist or isn't a data message.
+fn push_tcp_stream_handle<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
+    mut caller: Caller<T>,
+    message_id: u64,
+    stream_id: u64,
+) -> Result<u64, Trap> {
+    add_tcp_stream(
+        caller.data_mut(),
+        Target::Handle(message_id),
+        stream_id,
+        "lunatic::message::push_tcp_stream_handle",
+    )
+}
+
+// Takes the tcp stream from the message handle by index, puts it into the process' resources and
+// returns the resource ID.
+//
+// Traps:
+// * If index ID doesn't exist or matches the wrong resource (not a tcp stream).
+// * If the message ID doesn't exist or isn't a data message.
+fn take_tcp_stream_handle<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
+    mut caller: Caller<T>,
+    message_id: u64,
+    index: u64,
+) -> Result<u64, Trap> {
+    take_tcp_stream_from(
+        caller.data_mut(),
+        Target::Handle(message_id),
+        index,
+        "lunatic::message::take_tcp_stream_handle",
+    )
+}
+
+// Adds a udp socket resource to the message handle and returns the new location of it. This will
+// remove the socket from the current process' resources.
+//
+// Traps:
+// * If UDP socket ID doesn't exist
+// * If the message ID doesn't exist or isn't a data message.
+fn push_udp_socket_handle<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
+    mut caller: Caller<T>,
+    message_id: u64,
+    socket_id: u64,
+) -> Result<u64, Trap> {
+    add_udp_socket(
+        caller.data_mut(),
+        Target::Handle(message_id),
+        socket_id,
+        "lunatic::message::push_udp_socket_handle",
+    )
+}
+
+// Takes the udp socket from the message handle by index, puts it into the process' resources and
+// returns the resource ID.
+//
+// Traps:
+// * If index ID doesn't exist or matches the wrong resource (not a udp socket).
+// * If the message ID doesn't exist or isn't a data message.
+fn take_udp_socket_handle<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
+    mut caller: Caller<T>,
+    message_id: u64,
+    index: u64,
+) -> Result<u64, Trap> {
+    take_udp_socket_from(
+        caller.data_mut(),
+        Target::Handle(message_id),
+        index,
+        "lunatic::message::take_udp_socket_handle",
+    )
+}
+
+// Sends the message handle to a process. The handle is consumed if the message was sent.
+//
+// There are no guarantees that the message will be received.
+//
+// Returns:
+// * 0 if the message was sent.
+// * 1 if the receiving process asked senders to back off. The handle stays valid.
+//
+// Traps:
+// * If the process ID doesn't exist.
+// * If the message ID doesn't exist.
+fn send_handle<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    message_id: u64,
+    process_id: u64,
+) -> Result<u32, Trap> {
+    let process = caller
+        .data()
+        .process_resources()
+        .get(process_id)
+        .or_trap("lunatic::message::send_handle")?
+        .clone();
+    if process.back_pressure() {
+        return Ok(1);
+    }
+    let message = caller
+        .data_mut()
+        .message_resources_mut()
+        .remove(message_id)
+        .or_trap("lunatic::message::send_handle")?;
+    process.send(Signal::Message(message));
+    Ok(0)
+}
+
+// Creates a new data message counted against the memory of the process. The capacity hint is
+// capped at the maximum message size and the memory left to the process.
+fn new_data_message<T: ProcessState + ProcessCtx<T>>(
+    caller: &mut Caller<T>,
+    tag: Option<i64>,
+    buffer_capacity: u64,
+) -> Result<DataMessage, Trap> {
+    let memory_size = get_memory(caller)?.data_size(&caller);
+    let config = caller.data().config().clone();
+    let mailbox = caller.data_mut().mailbox();
+    let available = config
+        .get_max_memory()
+        .saturating_sub(memory_size + mailbox.memory().used());
+    let buffer_capacity = (buffer_capacity as usize)
+        .min(config.get_max_message_size().unwrap_or(usize::MAX))
+        .min(available);
+    let mut message = DataMessage::new(tag, buffer_capacity);
+    message.charge_to(mailbox.memory());
+    Ok(message)
+}
+
+// Checks if a message buffer can grow from **current** to **size** bytes without exceeding the
+// maximum message size or the memory limit of the process. Message buffers are counted against
+// the same limit as the linear memory.
+fn check_message_size<T: ProcessState + ProcessCtx<T>>(
+    caller: &mut Caller<T>,
+    current: usize,
+    size: usize,
+    fn_name: &str,
+) -> Result<(), Trap> {
+    let config = caller.data().config().clone();
+    if let Some(max_message_size) = config.get_max_message_size() {
+        if size > max_message_size {
+            return Err(Trap::new(format!(
+                "{}: Message exceeds the maximum size of {} bytes",
+                fn_name, max_message_size
+            )));
         }
+    }
+    let memory_size = get_memory(caller)?.data_size(&caller);
+    let message_memory = caller.data_mut().mailbox().memory().used();
+    let additional = size.saturating_sub(current);
+    if memory_size + message_memory + additional > config.get_max_memory() {
+        caller.data_mut().set_memory_limit_reached();
+        return Err(Trap::new(format!("{}: Memory limit exceeded", fn_name)));
+    }
+    Ok(())
+}
+
+// Locates the data message a host function works on.
+#[derive(Clone, Copy)]
+enum Target {
+    // The message in the scratch area.
+    ScratchArea,
+    // The message behind a handle created with `lunatic::message::create_data_handle` or
+    // `lunatic::message::scratch_area_to_handle`.
+    Handle(u64),
+}
+
+// Returns the data message of the target.
+fn data_message<'a, T: ProcessState + ProcessCtx<T>>(
+    state: &'a mut T,
+    target: Target,
+    fn_name: &str,
+) -> Result<&'a mut DataMessage, Trap> {
+    let message = match target {
+        Target::ScratchArea => state.message_scratch_area().as_mut().or_trap(fn_name)?,
+        Target::Handle(message_id) => state
+            .message_resources_mut()
+            .get_mut(message_id)
+            .or_trap(fn_name)?,
     };
-    Ok(caller.data_mut().udp_resources_mut().add(udp_socket))
+    data_message_mut(message)
+}
+
+// Returns the data message or traps if it's a different kind of message.
+fn data_message_mut(message: &mut Message) -> Result<&mut DataMessage, Trap> {
+    match message {
+        Message::Data(data) => Ok(data),
+        other => Err(Trap::new(format!(
+            "Unexpected `Message::{:?}`, expected `Message::Data`",
+            other.kind()
+        ))),
+    }
+}
+
+// Writes **data_len** bytes of guest memory at **data_ptr** into the buffer of the target message.
+fn write_to<T: ProcessState + ProcessCtx<T>>(
+    caller: &mut Caller<T>,
+    target: Target,
+    data_ptr: u32,
+    data_len: u32,
+    fn_name: &str,
+) -> Result<u32, Trap> {
+    let data = data_message(caller.data_mut(), target, fn_name)?;
+    let len = data.buffer.len();
+    check_message_size(caller, len, len + data_len as usize, fn_name)?;
+    let memory = get_memory(caller)?;
+    let (memory, state) = memory.data_and_store_mut(caller);
+    let buffer = memory
+        .get(data_ptr as usize..(data_ptr as usize + data_len as usize))
+        .or_trap(fn_name)?;
+    let bytes = data_message(state, target, fn_name)?
+        .write(buffer)
+        .or_trap(fn_name)?;
+    Ok(bytes as u32)
+}
+
+// Reads up to **data_len** bytes from the buffer of the target message into guest memory at
+// **data_ptr**.
+fn read_from<T: ProcessState + ProcessCtx<T>>(
+    caller: &mut Caller<T>,
+    target: Target,
+    data_ptr: u32,
+    data_len: u32,
+    fn_name: &str,
+) -> Result<u32, Trap> {
+    let memory = get_memory(caller)?;
+    let (memory, state) = memory.data_and_store_mut(caller);
+    let buffer = memory
+        .get_mut(data_ptr as usize..(data_ptr as usize + data_len as usize))
+        .or_trap(fn_name)?;
+    let bytes = data_message(state, target, fn_name)?
+        .read(buffer)
+        .or_trap(fn_name)?;
+    Ok(bytes as u32)
+}
+
+// Moves the process handle out of the process' resources into the target message.
+fn add_process<T: ProcessState + ProcessCtx<T>>(
+    state: &mut T,
+    target: Target,
+    process_id: u64,
+    fn_name: &str,
+) -> Result<u64, Trap> {
+    // Check the message first, so that the process isn't lost if there is none.
+    data_message(state, target, fn_name)?;
+    let process = state
+        .process_resources_mut()
+        .remove(process_id)
+        .or_trap(fn_name)?;
+    Ok(data_message(state, target, fn_name)?.add_process(process) as u64)
+}
+
+// Moves the process handle at **index** out of the target message into the process' resources.
+fn take_process_from<T: ProcessState + ProcessCtx<T>>(
+    state: &mut T,
+    target: Target,
+    index: u64,
+    fn_name: &str,
+) -> Result<u64, Trap> {
+    let process = data_message(state, target, fn_name)?
+        .take_process(index as usize)
+        .or_trap(fn_name)?;
+    Ok(state.process_resources_mut().add(process))
+}
+
+// Moves the tcp stream out of the process' resources into the target message.
+fn add_tcp_stream<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
+    state: &mut T,
+    target: Target,
+    stream_id: u64,
+    fn_name: &str,
+) -> Result<u64, Trap> {
+    // Check the message first, so that the stream isn't lost if there is none.
+    data_message(state, target, fn_name)?;
+    let stream = state
+        .tcp_stream_resources_mut()
+        .remove(stream_id)
+        .or_trap(fn_name)?;
+    Ok(data_message(state, target, fn_name)?.add_tcp_stream(stream) as u64)
+}
+
+// Moves the tcp stream at **index** out of the target message into the process' resources.
+fn take_tcp_stream_from<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
+    state: &mut T,
+    target: Target,
+    index: u64,
+    fn_name: &str,
+) -> Result<u64, Trap> {
+    let stream = data_message(state, target, fn_name)?
+        .take_tcp_stream(index as usize)
+        .or_trap(fn_name)?;
+    Ok(state.tcp_stream_resources_mut().add(stream))
+}
+
+// Moves the udp socket out of the process' resources into the target message.
+fn add_udp_socket<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
+    state: &mut T,
+    target: Target,
+    socket_id: u64,
+    fn_name: &str,
+) -> Result<u64, Trap> {
+    // Check the message first, so that the socket isn't lost if there is none.
+    data_message(state, target, fn_name)?;
+    let socket = state
+        .udp_resources_mut()
+        .remove(socket_id)
+        .or_trap(fn_name)?;
+    Ok(data_message(state, target, fn_name)?.add_udp_socket(socket) as u64)
+}
+
+// Moves the udp socket at **index** out of the target message into the process' resources.
+fn take_udp_socket_from<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
+    state: &mut T,
+    target: Target,
+    index: u64,
+    fn_name: &str,
+) -> Result<u64, Trap> {
+    let socket = data_message(state, target, fn_name)?
+        .take_udp_socket(index as usize)
+        .or_trap(fn_name)?;
+    Ok(state.udp_resources_mut().add(socket))
 }
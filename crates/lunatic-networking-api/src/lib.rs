@@ -18,7 +18,7 @@ use wasmtime::{Memory, Trap};
 
 use lunatic_common_api::{get_memory, IntoTrap};
 
-pub type TcpListenerResources = HashMapId<TcpListener>;
+pub type TcpListenerResources = HashMapId<Arc<TcpListener>>;
 pub type TcpStreamResources = HashMapId<TcpStream>;
 pub type UdpResources = HashMapId<Arc<UdpSocket>>;
 pub type DnsResources = HashMapId<DnsIterator>;
@@ -282,7 +282,10 @@ fn tcp_bind<T: NetworkingCtx + ErrorCtx + Send>(
         )?;
         let (tcp_listener_or_error_id, result) = match TcpListener::bind(socket_addr).await {
             Ok(listener) => (
-                caller.data_mut().tcp_listener_resources_mut().add(listener),
+                caller
+                    .data_mut()
+                    .tcp_listener_resources_mut()
+                    .add(Arc::new(listener)),
                 0,
             ),
             Err(error) => (caller.data_mut().error_resources_mut().add(error.into()), 1),
@@ -10,14 +10,27 @@ use hash_map_id::HashMapId;
 use lunatic_common_api::{get_memory, IntoTrap};
 use lunatic_error_api::ErrorCtx;
 use lunatic_process::{
-    config::ProcessConfig, mailbox::MessageMailbox, message::Message,
-    runtimes::wasmtime::WasmtimeCompiledModule, state::ProcessState, wasm::spawn_wasm, Process,
-    Signal, WasmProcess,
+    config::ProcessConfig,
+    dead_letters::DeadLetterSink,
+    info::{ProcessInfo, ProcessStatus},
+    mailbox::{MessageMailbox, OverflowPolicy},
+    message::{Message, SharedBuffer},
+    new_monitor_ref,
+    runtimes::wasmtime::WasmtimeCompiledModule,
+    state::ProcessState,
+    wasm::spawn_wasm,
+    Process, Signal, WasmProcess,
 };
 use lunatic_wasi_api::LunaticWasiCtx;
+use uuid::Uuid;
 use wasmtime::{Caller, Linker, ResourceLimiter, Trap, Val};
 
 pub type ProcessResources = HashMapId<Arc<dyn Process>>;
+pub type MessageResources = HashMapId<Message>;
+pub type SharedBufferResources = HashMapId<SharedBuffer>;
+
+// Link option: also notify the link if the process finishes normally.
+const LINK_NOTIFY_NORMAL_EXIT: u32 = 0x1;
 pub type ModuleResources<T> = HashMapId<WasmtimeCompiledModule<T>>;
 
 pub trait ProcessConfigCtx {
@@ -27,11 +40,17 @@ pub trait ProcessConfigCtx {
     fn set_can_create_configs(&mut self, can: bool);
     fn can_spawn_processes(&self) -> bool;
     fn set_can_spawn_processes(&mut self, can: bool);
+    fn can_inspect_processes(&self) -> bool;
+    fn set_can_inspect_processes(&mut self, can: bool);
 }
 
 pub trait ProcessCtx<S: ProcessState> {
     fn mailbox(&mut self) -> &mut MessageMailbox;
     fn message_scratch_area(&mut self) -> &mut Option<Message>;
+    fn message_resources(&self) -> &MessageResources;
+    fn message_resources_mut(&mut self) -> &mut MessageResources;
+    fn shared_buffer_resources(&self) -> &SharedBufferResources;
+    fn shared_buffer_resources_mut(&mut self) -> &mut SharedBufferResources;
     fn module_resources(&self) -> &ModuleResources<S>;
     fn module_resources_mut(&mut self) -> &mut ModuleResources<S>;
     fn process_resources(&self) -> &ProcessResources;
@@ -69,6 +88,56 @@ where
         "config_get_max_fuel",
         config_get_max_fuel,
     )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_set_mailbox_capacity",
+        config_set_mailbox_capacity,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_get_mailbox_capacity",
+        config_get_mailbox_capacity,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_set_mailbox_overflow_policy",
+        config_set_mailbox_overflow_policy,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_get_mailbox_overflow_policy",
+        config_get_mailbox_overflow_policy,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_set_max_message_size",
+        config_set_max_message_size,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_get_max_message_size",
+        config_get_max_message_size,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_set_max_execution_time",
+        config_set_max_execution_time,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_get_max_execution_time",
+        config_get_max_execution_time,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_set_max_cpu_time",
+        config_set_max_cpu_time,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_get_max_cpu_time",
+        config_get_max_cpu_time,
+    )?;
     linker.func_wrap(
         "lunatic::process",
         "config_can_compile_modules",
@@ -99,6 +168,16 @@ where
         "config_set_can_spawn_processes",
         config_set_can_spawn_processes,
     )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_can_inspect_processes",
+        config_can_inspect_processes,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_set_can_inspect_processes",
+        config_set_can_inspect_processes,
+    )?;
 
     linker.func_wrap8_async("lunatic::process", "spawn", spawn)?;
 
@@ -109,8 +188,45 @@ where
     linker.func_wrap("lunatic::process", "this", this)?;
 
     linker.func_wrap("lunatic::process", "id", id)?;
+    linker.func_wrap("lunatic::process", "info", info)?;
+    linker.func_wrap("lunatic::process", "parent", parent)?;
+    linker.func_wrap("lunatic::process", "children", children)?;
     linker.func_wrap("lunatic::process", "link", link)?;
+    linker.func_wrap("lunatic::process", "link_with_options", link_with_options)?;
     linker.func_wrap("lunatic::process", "unlink", unlink)?;
+    linker.func_wrap("lunatic::process", "monitor", monitor)?;
+    linker.func_wrap("lunatic::process", "demonitor", demonitor)?;
+    linker.func_wrap("lunatic::process", "kill", kill)?;
+    linker.func_wrap("lunatic::process", "shutdown", shutdown)?;
+    linker.func_wrap("lunatic::process", "suspend", suspend)?;
+    linker.func_wrap("lunatic::process", "resume", resume)?;
+
+    linker.func_wrap("lunatic::process", "list_processes", list_processes)?;
+    linker.func_wrap(
+        "lunatic::process",
+        "list_processes_by_module",
+        list_processes_by_module,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "list_processes_by_parent",
+        list_processes_by_parent,
+    )?;
+    linker.func_wrap("lunatic::process", "lookup_process", lookup_process)?;
+    linker.func_wrap(
+        "lunatic::process",
+        "set_dead_letter_sink",
+        set_dead_letter_sink,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "clear_dead_letter_sink",
+        clear_dead_letter_sink,
+    )?;
+    linker.func_wrap("lunatic::process", "dead_letter_stats", dead_letter_stats)?;
+    linker.func_wrap("lunatic::process", "join_group", join_group)?;
+    linker.func_wrap("lunatic::process", "leave_group", leave_group)?;
+    linker.func_wrap("lunatic::process", "group_members", group_members)?;
 
     Ok(())
 }
@@ -292,6 +408,262 @@ fn config_get_max_fuel<T: ProcessState + ProcessCtx<T>>(
     }
 }
 
+// Sets the maximum number of messages waiting in the mailbox of processes spawned from this
+// configuration.
+//
+// A value of 0 indicates an unbounded mailbox.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_set_mailbox_capacity<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    config_id: u64,
+    capacity: u64,
+) -> Result<(), Trap> {
+    let capacity = match capacity {
+        0 => None,
+        capacity => Some(capacity as usize),
+    };
+
+    caller
+        .data_mut()
+        .config_resources_mut()
+        .get_mut(config_id)
+        .or_trap("lunatic::process::config_set_mailbox_capacity: Config ID doesn't exist")?
+        .set_mailbox_capacity(capacity);
+    Ok(())
+}
+
+// Returns the mailbox capacity of a configuration.
+//
+// A value of 0 indicates an unbounded mailbox.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_get_mailbox_capacity<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    config_id: u64,
+) -> Result<u64, Trap> {
+    let capacity = caller
+        .data()
+        .config_resources()
+        .get(config_id)
+        .or_trap("lunatic::process::config_get_mailbox_capacity: Config ID doesn't exist")?
+        .get_mailbox_capacity();
+    match capacity {
+        None => Ok(0),
+        Some(capacity) => Ok(capacity as u64),
+    }
+}
+
+// Sets what happens if a message arrives at the full mailbox of processes spawned from this
+// configuration.
+//
+// Policies:
+// * 0 - Drop the incoming message.
+// * 1 - Drop the oldest message in the mailbox.
+// * 2 - Drop the incoming message and kill the receiving process.
+// * 3 - Return a back-pressure code from `lunatic::message::send` to senders once the mailbox is
+//       three quarters full. Messages arriving at the full mailbox are dropped.
+//
+// Traps:
+// * If the config ID doesn't exist.
+// * If the policy doesn't exist.
+fn config_set_mailbox_overflow_policy<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    config_id: u64,
+    policy: u32,
+) -> Result<(), Trap> {
+    let policy = match policy {
+        0 => OverflowPolicy::DropNewest,
+        1 => OverflowPolicy::DropOldest,
+        2 => OverflowPolicy::KillReceiver,
+        3 => OverflowPolicy::BackPressure,
+        _ => {
+            return Err(Trap::new(
+                "lunatic::process::config_set_mailbox_overflow_policy: Unknown policy",
+            ))
+        }
+    };
+
+    caller
+        .data_mut()
+        .config_resources_mut()
+        .get_mut(config_id)
+        .or_trap("lunatic::process::config_set_mailbox_overflow_policy: Config ID doesn't exist")?
+        .set_mailbox_overflow_policy(policy);
+    Ok(())
+}
+
+// Returns the mailbox overflow policy of a configuration. See
+// `config_set_mailbox_overflow_policy` for the values.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_get_mailbox_overflow_policy<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    config_id: u64,
+) -> Result<u32, Trap> {
+    let policy = caller
+        .data()
+        .config_resources()
+        .get(config_id)
+        .or_trap("lunatic::process::config_get_mailbox_overflow_policy: Config ID doesn't exist")?
+        .get_mailbox_overflow_policy();
+    let policy = match policy {
+        OverflowPolicy::DropNewest => 0,
+        OverflowPolicy::DropOldest => 1,
+        OverflowPolicy::KillReceiver => 2,
+        OverflowPolicy::BackPressure => 3,
+    };
+    Ok(policy)
+}
+
+// Sets the maximum size in bytes of a message buffer created by processes spawned from this
+// configuration.
+//
+// A value of 0 indicates no limit, other than the memory limit of the process.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_set_max_message_size<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    config_id: u64,
+    max_message_size: u64,
+) -> Result<(), Trap> {
+    let max_message_size = match max_message_size {
+        0 => None,
+        max_message_size => Some(max_message_size as usize),
+    };
+
+    caller
+        .data_mut()
+        .config_resources_mut()
+        .get_mut(config_id)
+        .or_trap("lunatic::process::config_set_max_message_size: Config ID doesn't exist")?
+        .set_max_message_size(max_message_size);
+    Ok(())
+}
+
+// Returns the maximum message size of a configuration.
+//
+// A value of 0 indicates no limit.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_get_max_message_size<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    config_id: u64,
+) -> Result<u64, Trap> {
+    let max_message_size = caller
+        .data()
+        .config_resources()
+        .get(config_id)
+        .or_trap("lunatic::process::config_get_max_message_size: Config ID doesn't exist")?
+        .get_max_message_size();
+    match max_message_size {
+        None => Ok(0),
+        Some(max_message_size) => Ok(max_message_size as u64),
+    }
+}
+
+// Sets the maximum execution time (wall-clock time) of processes spawned from this configuration
+// in milliseconds. A process exceeding it is killed.
+//
+// A value of 0 indicates no limit.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_set_max_execution_time<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    config_id: u64,
+    max_execution_time_ms: u64,
+) -> Result<(), Trap> {
+    let max_execution_time = match max_execution_time_ms {
+        0 => None,
+        max_execution_time_ms => Some(Duration::from_millis(max_execution_time_ms)),
+    };
+
+    caller
+        .data_mut()
+        .config_resources_mut()
+        .get_mut(config_id)
+        .or_trap("lunatic::process::config_set_max_execution_time: Config ID doesn't exist")?
+        .set_max_execution_time(max_execution_time);
+    Ok(())
+}
+
+// Returns the maximum execution time (wall-clock time) of a configuration in milliseconds.
+//
+// A value of 0 indicates no limit.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_get_max_execution_time<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    config_id: u64,
+) -> Result<u64, Trap> {
+    let max_execution_time = caller
+        .data()
+        .config_resources()
+        .get(config_id)
+        .or_trap("lunatic::process::config_get_max_execution_time: Config ID doesn't exist")?
+        .get_max_execution_time();
+    match max_execution_time {
+        None => Ok(0),
+        Some(max_execution_time) => Ok(max_execution_time.as_millis() as u64),
+    }
+}
+
+// Sets the maximum CPU time (time spent running between yields) of processes spawned from this
+// configuration in milliseconds. A process exceeding it is killed.
+//
+// A value of 0 indicates no limit.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_set_max_cpu_time<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    config_id: u64,
+    max_cpu_time_ms: u64,
+) -> Result<(), Trap> {
+    let max_cpu_time = match max_cpu_time_ms {
+        0 => None,
+        max_cpu_time_ms => Some(Duration::from_millis(max_cpu_time_ms)),
+    };
+
+    caller
+        .data_mut()
+        .config_resources_mut()
+        .get_mut(config_id)
+        .or_trap("lunatic::process::config_set_max_cpu_time: Config ID doesn't exist")?
+        .set_max_cpu_time(max_cpu_time);
+    Ok(())
+}
+
+// Returns the maximum CPU time (time spent running between yields) of a configuration in
+// milliseconds.
+//
+// A value of 0 indicates no limit.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_get_max_cpu_time<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    config_id: u64,
+) -> Result<u64, Trap> {
+    let max_cpu_time = caller
+        .data()
+        .config_resources()
+        .get(config_id)
+        .or_trap("lunatic::process::config_get_max_cpu_time: Config ID doesn't exist")?
+        .get_max_cpu_time();
+    match max_cpu_time {
+        None => Ok(0),
+        Some(max_cpu_time) => Ok(max_cpu_time.as_millis() as u64),
+    }
+}
+
 // Returns 1 if processes spawned from this configuration can compile Wasm modules, otherwise 0.
 //
 // Traps:
@@ -416,6 +788,48 @@ where
     Ok(())
 }
 
+// Returns 1 if processes spawned from this configuration can inspect all processes of the runtime,
+// otherwise 0.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_can_inspect_processes<T>(caller: Caller<T>, config_id: u64) -> Result<u32, Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    let can = caller
+        .data()
+        .config_resources()
+        .get(config_id)
+        .or_trap("lunatic::process::config_can_inspect_processes: Config ID doesn't exist")?
+        .can_inspect_processes();
+    Ok(can as u32)
+}
+
+// If set to a value >0 (true), processes spawned from this configuration will be able to list and
+// look up all processes of the runtime.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_set_can_inspect_processes<T>(
+    mut caller: Caller<T>,
+    config_id: u64,
+    can: u32,
+) -> Result<(), Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    caller
+        .data_mut()
+        .config_resources_mut()
+        .get_mut(config_id)
+        .or_trap("lunatic::process::config_set_can_inspect_processes: Config ID doesn't exist")?
+        .set_can_inspect_processes(can != 0);
+    Ok(())
+}
+
 // Spawns a new process using the passed in function inside a module as the entry point.
 //
 // If **link** is not 0, it will link the child and parent processes. The value of the **link**
@@ -514,20 +928,29 @@ where
                 Ok(result)
             })
             .collect::<Result<Vec<_>>>()?;
+        // Create handle to itself, the child keeps track of its parent.
+        let id = caller.data().id();
+        let signal_mailbox = caller.data().signal_mailbox().clone();
+        let info = caller.data().process_info().clone();
+        let dead_letters = caller.data().process_table().dead_letters().clone();
+        let this_process: Arc<dyn Process> =
+            Arc::new(WasmProcess::new(id, signal_mailbox.0, info, dead_letters));
         // Should processes be linked together?
         let link: Option<(Option<i64>, Arc<dyn Process>)> = match link {
             0 => None,
-            tag => {
-                let id = caller.data().id();
-                let signal_mailbox = caller.data().signal_mailbox().clone();
-                let process = WasmProcess::new(id, signal_mailbox.0);
-                Some((Some(tag), Arc::new(process)))
-            }
+            tag => Some((Some(tag), this_process.clone())),
         };
 
         let runtime = caller.data().runtime().clone();
         let registry = caller.data().registry().clone();
-        let mut state = T::new(runtime.clone(), module.clone(), config, registry)?;
+        let process_table = caller.data().process_table().clone();
+        let mut state = T::new(
+            runtime.clone(),
+            module.clone(),
+            config,
+            registry,
+            process_table,
+        )?;
 
         // Inherit stdout and stderr streams if they are redirected by the parent.
         let stdout = if let Some(stdout) = caller.data().get_stdout() {
@@ -550,8 +973,9 @@ where
             }
         }
 
+        let parent = Some(this_process);
         let (proc_or_error_id, result) =
-            match spawn_wasm(runtime, module, state, function, params, link).await {
+            match spawn_wasm(runtime, module, state, function, params, link, parent).await {
                 Ok((_, process)) => (caller.data_mut().process_resources_mut().add(process), 0),
                 Err(error) => (caller.data_mut().error_resources_mut().add(error), 1),
             };
@@ -605,11 +1029,15 @@ fn clone_process<T: ProcessState + ProcessCtx<T>>(
 //
 // Suspend process for `millis`.
 fn sleep_ms<T: ProcessState + ProcessCtx<T>>(
-    _: Caller<T>,
+    caller: Caller<T>,
     millis: u64,
 ) -> Box<dyn Future<Output = ()> + Send + '_> {
+    let info = caller.data().process_info().clone();
+    info.set_fuel_consumed(caller.fuel_consumed().unwrap_or(0));
+    info.set_status(ProcessStatus::Sleeping);
     Box::new(async move {
         async_std::task::sleep(Duration::from_millis(millis)).await;
+        info.set_status(ProcessStatus::Running);
     })
 }
 
@@ -619,7 +1047,8 @@ fn sleep_ms<T: ProcessState + ProcessCtx<T>>(
 // 1. `trap == 0` the received signal will be turned into a signal message and put into the mailbox.
 // 2. `trap != 0` the process will die and notify all linked processes of its death.
 //
-// The default behaviour for a newly spawned process is 2.
+// The default behaviour for a newly spawned process is 2. A link that finished normally never
+// kills the process, the notification is always turned into a message.
 fn die_when_link_dies<T: ProcessState + ProcessCtx<T>>(mut caller: Caller<T>, trap: u32) {
     caller
         .data_mut()
@@ -633,7 +1062,9 @@ fn die_when_link_dies<T: ProcessState + ProcessCtx<T>>(mut caller: Caller<T>, tr
 fn this<T: ProcessState + ProcessCtx<T>>(mut caller: Caller<T>) -> u64 {
     let id = caller.data().id();
     let signal_mailbox = caller.data().signal_mailbox().clone();
-    let process = WasmProcess::new(id, signal_mailbox.0);
+    let info = caller.data().process_info().clone();
+    let dead_letters = caller.data().process_table().dead_letters().clone();
+    let process = WasmProcess::new(id, signal_mailbox.0, info, dead_letters);
     caller
         .data_mut()
         .process_resources_mut()
@@ -664,40 +1095,193 @@ fn id<T: ProcessState + ProcessCtx<T>>(
     Ok(())
 }
 
-// Link current process to **process_id**. This is not an atomic operation, any of the 2 processes
-// could fail before processing the `Link` signal and may not notify the other.
+// Writes runtime information about **process_id** to **info_ptr**.
+//
+// The information is written as 6 consecutive little endian `u64` values (48 bytes):
+// * status - 0 running, 1 waiting on a message, 2 sleeping, 3 finished, 4 suspended.
+// * mailbox length - Number of messages waiting in the mailbox.
+// * memory size - Size of the linear memory in bytes.
+// * fuel consumed - Fuel used up by the process the last time it blocked.
+// * links - Number of linked processes.
+// * spawn time - Milliseconds since the UNIX epoch when the process was spawned.
 //
 // Traps:
 // * If the process ID doesn't exist.
-fn link<T: ProcessState + ProcessCtx<T>>(
+// * If **info_ptr + 48** is outside the memory.
+fn info<T: ProcessState + ProcessCtx<T>>(
     mut caller: Caller<T>,
-    tag: i64,
     process_id: u64,
+    info_ptr: u32,
 ) -> Result<(), Trap> {
-    let tag = match tag {
-        0 => None,
-        tag => Some(tag),
-    };
-    // Create handle to itself
-    let id = caller.data().id();
-    let signal_mailbox = caller.data().signal_mailbox().clone();
-    let this_process = WasmProcess::new(id, signal_mailbox.0);
-
-    // Send link signal to other process
+    // Make the fuel usage up to date if a process is asking about itself.
+    let own_fuel = caller.fuel_consumed().unwrap_or(0);
     let process = caller
         .data()
         .process_resources()
         .get(process_id)
-        .or_trap("lunatic::process::link")?
+        .or_trap("lunatic::process::info")?
         .clone();
-    process.send(Signal::Link(tag, Arc::new(this_process)));
-
-    // Send link signal to itself
-    caller
-        .data_mut()
+    let ProcessInfo {
+        id,
+        status,
+        mailbox_len,
+        memory_size,
+        fuel_consumed,
+        links,
+        spawned_at,
+        ..
+    } = process.info();
+    let fuel_consumed = if id == caller.data().id() {
+        own_fuel
+    } else {
+        fuel_consumed
+    };
+    let status: u64 = match status {
+        ProcessStatus::Running => 0,
+        ProcessStatus::Waiting => 1,
+        ProcessStatus::Sleeping => 2,
+        ProcessStatus::Finished => 3,
+        ProcessStatus::Suspended => 4,
+    };
+    let spawned_at = spawned_at
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let buffer: Vec<u8> = [
+        status,
+        mailbox_len as u64,
+        memory_size as u64,
+        fuel_consumed,
+        links as u64,
+        spawned_at,
+    ]
+    .iter()
+    .flat_map(|value| value.to_le_bytes())
+    .collect();
+    let memory = get_memory(&mut caller)?;
+    memory
+        .write(&mut caller, info_ptr as usize, &buffer)
+        .or_trap("lunatic::process::info")?;
+    Ok(())
+}
+
+// Writes the UUID of the process that spawned **process_id** to **u128_ptr**.
+//
+// Returns:
+// * 0 if the process has a parent.
+// * 1 if the process was spawned by the runtime and has no parent.
+//
+// Traps:
+// * If the process ID doesn't exist.
+// * If any memory outside the guest heap space is referenced.
+fn parent<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    process_id: u64,
+    u128_ptr: u32,
+) -> Result<u32, Trap> {
+    let parent = caller
+        .data()
+        .process_resources()
+        .get(process_id)
+        .or_trap("lunatic::process::parent")?
+        .info()
+        .parent;
+    let parent = match parent {
+        Some(parent) => parent.as_u128(),
+        None => return Ok(1),
+    };
+    let memory = get_memory(&mut caller)?;
+    memory
+        .write(&mut caller, u128_ptr as usize, &parent.to_le_bytes())
+        .or_trap("lunatic::process::parent")?;
+    Ok(0)
+}
+
+// Writes the UUIDs of all still running processes spawned by **process_id** to **uuids_ptr**, as
+// an array of little endian `u128` values. At most **uuids_len** IDs are written.
+//
+// Returns:
+// * The number of children, that can be bigger than **uuids_len**.
+//
+// Traps:
+// * If the process ID doesn't exist.
+// * If any memory outside the guest heap space is referenced.
+fn children<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    process_id: u64,
+    uuids_ptr: u32,
+    uuids_len: u32,
+) -> Result<u64, Trap> {
+    let children = caller
+        .data()
+        .process_resources()
+        .get(process_id)
+        .or_trap("lunatic::process::children")?
+        .info()
+        .children;
+    write_process_ids(caller, &children, uuids_ptr, uuids_len, "children")
+}
+
+// Link current process to **process_id**. This is not an atomic operation, any of the 2 processes
+// could fail before processing the `Link` signal and may not notify the other.
+//
+// Traps:
+// * If the process ID doesn't exist.
+fn link<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    tag: i64,
+    process_id: u64,
+) -> Result<(), Trap> {
+    link_with_options(caller, tag, process_id, 0)
+}
+
+// Link current process to **process_id** with additional link options. This is not an atomic
+// operation, any of the 2 processes could fail before processing the `Link` signal and may not
+// notify the other.
+//
+// **options** is a bit field:
+// * 0x1 - Notify the other side also if a process finishes normally. A `LinkDied` message with a
+//         normal exit reason is received in this case and it never kills the receiving process.
+//
+// Traps:
+// * If the process ID doesn't exist.
+fn link_with_options<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    tag: i64,
+    process_id: u64,
+    options: u32,
+) -> Result<(), Trap> {
+    let notify_normal_exit = options & LINK_NOTIFY_NORMAL_EXIT != 0;
+    let tag = match tag {
+        0 => None,
+        tag => Some(tag),
+    };
+    // Create handle to itself
+    let id = caller.data().id();
+    let signal_mailbox = caller.data().signal_mailbox().clone();
+    let info = caller.data().process_info().clone();
+    let dead_letters = caller.data().process_table().dead_letters().clone();
+    let this_process = WasmProcess::new(id, signal_mailbox.0, info, dead_letters);
+
+    // Send link signal to other process
+    let process = caller
+        .data()
+        .process_resources()
+        .get(process_id)
+        .or_trap("lunatic::process::link")?
+        .clone();
+    process.send(Signal::Link(
+        tag,
+        Arc::new(this_process),
+        notify_normal_exit,
+    ));
+
+    // Send link signal to itself
+    caller
+        .data_mut()
         .signal_mailbox()
         .0
-        .try_send(Signal::Link(tag, process))
+        .try_send(Signal::Link(tag, process, notify_normal_exit))
         .expect("The signal is sent to itself and the receiver must exist at this point");
     Ok(())
 }
@@ -713,7 +1297,9 @@ fn unlink<T: ProcessState + ProcessCtx<T>>(
     // Create handle to itself
     let id = caller.data().id();
     let signal_mailbox = caller.data().signal_mailbox().clone();
-    let this_process = WasmProcess::new(id, signal_mailbox.0);
+    let info = caller.data().process_info().clone();
+    let dead_letters = caller.data().process_table().dead_letters().clone();
+    let this_process = WasmProcess::new(id, signal_mailbox.0, info, dead_letters);
 
     // Send unlink signal to other process
     let process = caller
@@ -733,3 +1319,490 @@ fn unlink<T: ProcessState + ProcessCtx<T>>(
         .expect("The signal is sent to itself and the receiver must exist at this point");
     Ok(())
 }
+
+// Monitor **process_id** from the current process and return the monitor reference.
+//
+// Once the monitored process finishes, for any reason, a `ProcessDown` message containing the
+// monitor reference is sent to the current process. Unlike links, monitors are one-way and the
+// death of the monitored process never kills the current process.
+//
+// If the process already finished, the `ProcessDown` message is received right away and carries
+// the "no process" exit reason. `lunatic::message::receive_select` can wait on the message of a
+// specific monitor.
+//
+// Traps:
+// * If the process ID doesn't exist.
+fn monitor<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    process_id: u64,
+) -> Result<u64, Trap> {
+    // Create handle to itself
+    let id = caller.data().id();
+    let signal_mailbox = caller.data().signal_mailbox().clone();
+    let info = caller.data().process_info().clone();
+    let dead_letters = caller.data().process_table().dead_letters().clone();
+    let this_process = WasmProcess::new(id, signal_mailbox.0, info, dead_letters);
+
+    // Send monitor signal to other process
+    let monitor_ref = new_monitor_ref();
+    caller
+        .data()
+        .process_resources()
+        .get(process_id)
+        .or_trap("lunatic::process::monitor")?
+        .send(Signal::Monitor(monitor_ref, Arc::new(this_process)));
+    Ok(monitor_ref)
+}
+
+// Remove the monitor **monitor_ref** from **process_id**. This is not an atomic operation, a
+// `ProcessDown` message could already be on its way to the current process.
+//
+// Traps:
+// * If the process ID doesn't exist.
+fn demonitor<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    process_id: u64,
+    monitor_ref: u64,
+) -> Result<(), Trap> {
+    caller
+        .data()
+        .process_resources()
+        .get(process_id)
+        .or_trap("lunatic::process::demonitor")?
+        .send(Signal::Demonitor(monitor_ref));
+    Ok(())
+}
+
+// Kill **process_id** immediately. Linked and monitoring processes will see the current process
+// as the cause of the kill and, if **reason** is different from 0, the reason.
+//
+// Traps:
+// * If the process ID doesn't exist.
+fn kill<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    process_id: u64,
+    reason: i64,
+) -> Result<(), Trap> {
+    let reason = match reason {
+        0 => None,
+        reason => Some(reason),
+    };
+    let id = caller.data().id();
+    caller
+        .data()
+        .process_resources()
+        .get(process_id)
+        .or_trap("lunatic::process::kill")?
+        .send(Signal::Kill(Some(id), reason));
+    Ok(())
+}
+
+// Ask **process_id** to shut down gracefully.
+//
+// The process will receive a `Shutdown` message ahead of all other messages and can use it to
+// clean up (flush buffers, close connections, ...). If the process didn't finish after
+// **timeout_ms** milliseconds it's killed and its links and monitors are notified with a shutdown
+// timeout reason.
+//
+// Traps:
+// * If the process ID doesn't exist.
+fn shutdown<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    process_id: u64,
+    timeout_ms: u64,
+) -> Result<(), Trap> {
+    caller
+        .data()
+        .process_resources()
+        .get(process_id)
+        .or_trap("lunatic::process::shutdown")?
+        .send(Signal::Shutdown {
+            timeout: Duration::from_millis(timeout_ms),
+        });
+    Ok(())
+}
+
+// Suspend **process_id**. A suspended process doesn't run, but it still receives messages and
+// can be killed. Suspending an already suspended process has no effect.
+//
+// Traps:
+// * If the process ID doesn't exist.
+fn suspend<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    process_id: u64,
+) -> Result<(), Trap> {
+    caller
+        .data()
+        .process_resources()
+        .get(process_id)
+        .or_trap("lunatic::process::suspend")?
+        .send(Signal::Suspend);
+    Ok(())
+}
+
+// Resume the suspended **process_id**. Resuming a process that is not suspended has no effect.
+//
+// Traps:
+// * If the process ID doesn't exist.
+fn resume<T: ProcessState + ProcessCtx<T>>(caller: Caller<T>, process_id: u64) -> Result<(), Trap> {
+    caller
+        .data()
+        .process_resources()
+        .get(process_id)
+        .or_trap("lunatic::process::resume")?
+        .send(Signal::Resume);
+    Ok(())
+}
+
+// Writes the IDs of all live processes of the runtime to **uuids_ptr**, as an array of little
+// endian `u128` values. At most **uuids_len** IDs are written.
+//
+// Returns:
+// * The number of live processes, that can be bigger than **uuids_len**.
+//
+// Traps:
+// * If the process doesn't have permission to inspect processes.
+// * If any memory outside the guest heap space is referenced.
+fn list_processes<T>(caller: Caller<T>, uuids_ptr: u32, uuids_len: u32) -> Result<u64, Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    if !caller.data().config().can_inspect_processes() {
+        return Err(anyhow!("Process doesn't have permissions to inspect processes").into());
+    }
+    let ids: Vec<Uuid> = caller
+        .data()
+        .process_table()
+        .list()
+        .iter()
+        .map(|entry| entry.process.id())
+        .collect();
+    write_process_ids(caller, &ids, uuids_ptr, uuids_len, "list_processes")
+}
+
+// Writes the IDs of all live processes spawned from **module_id** to **uuids_ptr**, as an array
+// of little endian `u128` values. At most **uuids_len** IDs are written.
+//
+// If **module_id** has the value -1, the module of the process calling this function is used.
+//
+// Returns:
+// * The number of matching processes, that can be bigger than **uuids_len**.
+//
+// Traps:
+// * If the process doesn't have permission to inspect processes.
+// * If the module ID doesn't exist.
+// * If any memory outside the guest heap space is referenced.
+fn list_processes_by_module<T>(
+    caller: Caller<T>,
+    module_id: i64,
+    uuids_ptr: u32,
+    uuids_len: u32,
+) -> Result<u64, Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    if !caller.data().config().can_inspect_processes() {
+        return Err(anyhow!("Process doesn't have permissions to inspect processes").into());
+    }
+    let module_id = match module_id {
+        -1 => caller.data().module().id(),
+        module_id => caller
+            .data()
+            .module_resources()
+            .get(module_id as u64)
+            .or_trap("lunatic::process::list_processes_by_module: Module ID doesn't exist")?
+            .id(),
+    };
+    let ids: Vec<Uuid> = caller
+        .data()
+        .process_table()
+        .by_module(module_id)
+        .iter()
+        .map(|entry| entry.process.id())
+        .collect();
+    write_process_ids(
+        caller,
+        &ids,
+        uuids_ptr,
+        uuids_len,
+        "list_processes_by_module",
+    )
+}
+
+// Writes the IDs of all live processes spawned by the process with the UUID at **parent_ptr** to
+// **uuids_ptr**, as an array of little endian `u128` values. At most **uuids_len** IDs are
+// written.
+//
+// Returns:
+// * The number of matching processes, that can be bigger than **uuids_len**.
+//
+// Traps:
+// * If the process doesn't have permission to inspect processes.
+// * If any memory outside the guest heap space is referenced.
+fn list_processes_by_parent<T>(
+    mut caller: Caller<T>,
+    parent_ptr: u32,
+    uuids_ptr: u32,
+    uuids_len: u32,
+) -> Result<u64, Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    if !caller.data().config().can_inspect_processes() {
+        return Err(anyhow!("Process doesn't have permissions to inspect processes").into());
+    }
+    let parent = read_uuid(&mut caller, parent_ptr, "list_processes_by_parent")?;
+    let ids: Vec<Uuid> = caller
+        .data()
+        .process_table()
+        .by_parent(parent)
+        .iter()
+        .map(|entry| entry.process.id())
+        .collect();
+    write_process_ids(
+        caller,
+        &ids,
+        uuids_ptr,
+        uuids_len,
+        "list_processes_by_parent",
+    )
+}
+
+// Looks up the live process with the UUID at **uuid_ptr** and returns 0 if it was found or 1 if
+// not found. If found, the ID of the process handle is written to **id_ptr**.
+//
+// Traps:
+// * If the process doesn't have permission to inspect processes.
+// * If any memory outside the guest heap space is referenced.
+fn lookup_process<T>(mut caller: Caller<T>, uuid_ptr: u32, id_ptr: u32) -> Result<u32, Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    if !caller.data().config().can_inspect_processes() {
+        return Err(anyhow!("Process doesn't have permissions to inspect processes").into());
+    }
+    let uuid = read_uuid(&mut caller, uuid_ptr, "lookup_process")?;
+    let process = match caller.data().process_table().get(uuid) {
+        Some(process) => process,
+        None => return Ok(1),
+    };
+    let process_id = caller.data_mut().process_resources_mut().add(process);
+    let memory = get_memory(&mut caller)?;
+    memory
+        .write(&mut caller, id_ptr as usize, &process_id.to_le_bytes())
+        .or_trap("lunatic::process::lookup_process")?;
+    Ok(0)
+}
+
+// Registers the process as the runtime-wide dead-letter sink. Messages that can't be delivered,
+// because the receiving process already finished, are forwarded to it as `DeadLetter` messages.
+// A previously registered sink is replaced.
+//
+// Traps:
+// * If the process doesn't have permission to inspect processes.
+// * If the process ID doesn't exist.
+fn set_dead_letter_sink<T>(caller: Caller<T>, process_id: u64) -> Result<(), Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    if !caller.data().config().can_inspect_processes() {
+        return Err(anyhow!("Process doesn't have permissions to inspect processes").into());
+    }
+    let process = caller
+        .data()
+        .process_resources()
+        .get(process_id)
+        .or_trap("lunatic::process::set_dead_letter_sink: Process ID doesn't exist")?
+        .clone();
+    caller
+        .data()
+        .process_table()
+        .dead_letters()
+        .set_sink(Some(DeadLetterSink::Process(process)));
+    Ok(())
+}
+
+// Removes the runtime-wide dead-letter sink. Undeliverable messages are only counted afterwards.
+//
+// Traps:
+// * If the process doesn't have permission to inspect processes.
+fn clear_dead_letter_sink<T>(caller: Caller<T>) -> Result<(), Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    if !caller.data().config().can_inspect_processes() {
+        return Err(anyhow!("Process doesn't have permissions to inspect processes").into());
+    }
+    caller.data().process_table().dead_letters().set_sink(None);
+    Ok(())
+}
+
+// Writes the dead-letter counters of the runtime to **stats_ptr**. The 16 bytes are two little
+// endian `u64` values:
+// * The number of messages that couldn't be delivered.
+// * The number of those messages that were forwarded to the dead-letter sink.
+//
+// Traps:
+// * If the process doesn't have permission to inspect processes.
+// * If any memory outside the guest heap space is referenced.
+fn dead_letter_stats<T>(mut caller: Caller<T>, stats_ptr: u32) -> Result<(), Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    if !caller.data().config().can_inspect_processes() {
+        return Err(anyhow!("Process doesn't have permissions to inspect processes").into());
+    }
+    let stats = caller.data().process_table().dead_letters().stats();
+    let mut buffer = [0; 16];
+    buffer[..8].copy_from_slice(&stats.undelivered.to_le_bytes());
+    buffer[8..].copy_from_slice(&stats.forwarded.to_le_bytes());
+    let memory = get_memory(&mut caller)?;
+    memory
+        .write(&mut caller, stats_ptr as usize, &buffer)
+        .or_trap("lunatic::process::dead_letter_stats")?;
+    Ok(())
+}
+
+// Adds **process_id** to the process group with the name at **name_str_ptr**. The process leaves
+// all groups once it finishes.
+//
+// Returns:
+// * 0 if the process joined the group.
+// * 1 if the process already finished.
+//
+// Traps:
+// * If the process ID doesn't exist.
+// * If the name is not a valid utf8 string.
+// * If any memory outside the guest heap space is referenced.
+fn join_group<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+    process_id: u64,
+) -> Result<u32, Trap> {
+    let process = caller
+        .data()
+        .process_resources()
+        .get(process_id)
+        .or_trap("lunatic::process::join_group")?
+        .clone();
+    let name = read_group_name(&mut caller, name_str_ptr, name_str_len, "join_group")?;
+    if caller.data().process_table().join_group(&name, process) {
+        Ok(0)
+    } else {
+        Ok(1)
+    }
+}
+
+// Removes **process_id** from the process group with the name at **name_str_ptr**.
+//
+// Returns:
+// * 0 if the process left the group.
+// * 1 if the process wasn't a member of the group.
+//
+// Traps:
+// * If the process ID doesn't exist.
+// * If the name is not a valid utf8 string.
+// * If any memory outside the guest heap space is referenced.
+fn leave_group<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+    process_id: u64,
+) -> Result<u32, Trap> {
+    let id = caller
+        .data()
+        .process_resources()
+        .get(process_id)
+        .or_trap("lunatic::process::leave_group")?
+        .id();
+    let name = read_group_name(&mut caller, name_str_ptr, name_str_len, "leave_group")?;
+    let left = caller.data().process_table().groups().leave(&name, id);
+    Ok(!left as u32)
+}
+
+// Writes the IDs of all members of the process group with the name at **name_str_ptr** to
+// **uuids_ptr**, as an array of little endian `u128` values. At most **uuids_len** IDs are
+// written.
+//
+// Returns:
+// * The number of members, that can be bigger than **uuids_len**.
+//
+// Traps:
+// * If the name is not a valid utf8 string.
+// * If any memory outside the guest heap space is referenced.
+fn group_members<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+    uuids_ptr: u32,
+    uuids_len: u32,
+) -> Result<u64, Trap> {
+    let name = read_group_name(&mut caller, name_str_ptr, name_str_len, "group_members")?;
+    let ids: Vec<Uuid> = caller
+        .data()
+        .process_table()
+        .groups()
+        .members(&name)
+        .iter()
+        .map(|process| process.id())
+        .collect();
+    write_process_ids(caller, &ids, uuids_ptr, uuids_len, "group_members")
+}
+
+// Reads the utf8 name of a process group from **name_str_ptr**.
+fn read_group_name<T: ProcessState + ProcessCtx<T>>(
+    caller: &mut Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+    function: &str,
+) -> Result<String, Trap> {
+    let memory = get_memory(caller)?;
+    let name = memory
+        .data(&caller)
+        .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
+        .or_trap(format!("lunatic::process::{}", function))?;
+    let name = std::str::from_utf8(name).or_trap(format!("lunatic::process::{}", function))?;
+    Ok(name.to_owned())
+}
+
+// Reads a little endian `u128` UUID from **uuid_ptr**.
+fn read_uuid<T: ProcessState + ProcessCtx<T>>(
+    caller: &mut Caller<T>,
+    uuid_ptr: u32,
+    function: &str,
+) -> Result<Uuid, Trap> {
+    let memory = get_memory(caller)?;
+    let mut uuid = [0; 16];
+    memory
+        .read(&caller, uuid_ptr as usize, &mut uuid)
+        .or_trap(format!("lunatic::process::{}", function))?;
+    Ok(Uuid::from_u128(u128::from_le_bytes(uuid)))
+}
+
+// Writes up to **uuids_len** process IDs to **uuids_ptr** and returns the number of processes.
+fn write_process_ids<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    ids: &[Uuid],
+    uuids_ptr: u32,
+    uuids_len: u32,
+    function: &str,
+) -> Result<u64, Trap> {
+    let buffer: Vec<u8> = ids
+        .iter()
+        .take(uuids_len as usize)
+        .flat_map(|id| id.as_u128().to_le_bytes())
+        .collect();
+    let memory = get_memory(&mut caller)?;
+    memory
+        .write(&mut caller, uuids_ptr as usize, &buffer)
+        .or_trap(format!("lunatic::process::{}", function))?;
+    Ok(ids.len() as u64)
+}
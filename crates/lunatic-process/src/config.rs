@@ -1,5 +1,9 @@
+use std::time::Duration;
+
 use serde::{de::DeserializeOwned, Serialize};
 
+use crate::mailbox::OverflowPolicy;
+
 // One unit of fuel represents around 100k instructions.
 pub const UNIT_OF_COMPUTE_IN_INSTRUCTIONS: u64 = 100_000;
 
@@ -12,8 +16,9 @@ pub const UNIT_OF_COMPUTE_IN_INSTRUCTIONS: u64 = 100_000;
 /// the process. This host functions are the ones that consider specific configuration while
 /// performing operations.
 ///
-/// However, two properties of a process are enforced by the runtime (maximum memory and maximum
-/// fuel usage). This two properties need to be part of every configuration.
+/// However, some properties of a process are enforced by the runtime (maximum memory, maximum
+/// fuel usage, mailbox capacity, maximum message size and time limits). This properties need to be
+/// part of every configuration.
 ///
 /// `ProcessConfig` must be serializable in case it is used to spawn processes on other nodes.
 pub trait ProcessConfig: Clone + Serialize + DeserializeOwned {
@@ -21,4 +26,14 @@ pub trait ProcessConfig: Clone + Serialize + DeserializeOwned {
     fn get_max_fuel(&self) -> Option<u64>;
     fn set_max_memory(&mut self, max_memory: usize);
     fn get_max_memory(&self) -> usize;
+    fn set_mailbox_capacity(&mut self, capacity: Option<usize>);
+    fn get_mailbox_capacity(&self) -> Option<usize>;
+    fn set_mailbox_overflow_policy(&mut self, policy: OverflowPolicy);
+    fn get_mailbox_overflow_policy(&self) -> OverflowPolicy;
+    fn set_max_message_size(&mut self, max_message_size: Option<usize>);
+    fn get_max_message_size(&self) -> Option<usize>;
+    fn set_max_execution_time(&mut self, max_execution_time: Option<Duration>);
+    fn get_max_execution_time(&self) -> Option<Duration>;
+    fn set_max_cpu_time(&mut self, max_cpu_time: Option<Duration>);
+    fn get_max_cpu_time(&self) -> Option<Duration>;
 }
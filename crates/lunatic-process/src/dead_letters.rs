@@ -0,0 +1,241 @@
+use std::fmt::Debug;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, RwLock,
+};
+
+use uuid::Uuid;
+
+use crate::{message::Message, ExitReason, Process, Signal};
+
+/// Receives messages that couldn't be delivered.
+#[derive(Clone)]
+pub enum DeadLetterSink {
+    /// The messages are forwarded to a process, wrapped in a [`Message::DeadLetter`].
+    Process(Arc<dyn Process>),
+    /// The callback is called with the ID of the intended recipient and the message.
+    Callback(Arc<dyn Fn(Uuid, Message) + Send + Sync>),
+}
+
+impl Debug for DeadLetterSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Process(process) => f.debug_tuple("Process").field(&process.id()).finish(),
+            Self::Callback(_) => f.debug_tuple("Callback").finish(),
+        }
+    }
+}
+
+/// Counters of undeliverable messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadLetterStats {
+    /// Number of messages that couldn't be delivered.
+    pub undelivered: u64,
+    /// Number of undelivered messages that were handed to the sink.
+    pub forwarded: u64,
+}
+
+/// The `DeadLetters` collect messages sent to processes that already finished.
+///
+/// They are shared by all processes of a runtime. Without a [`DeadLetterSink`] undeliverable
+/// messages are only counted and dropped.
+#[derive(Clone, Default)]
+pub struct DeadLetters {
+    inner: Arc<InnerDeadLetters>,
+}
+
+#[derive(Default)]
+struct InnerDeadLetters {
+    sink: RwLock<Option<DeadLetterSink>>,
+    undelivered: AtomicU64,
+    forwarded: AtomicU64,
+}
+
+impl Debug for DeadLetters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeadLetters")
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl DeadLetters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the sink receiving all undeliverable messages, or removes it if `sink` is `None`.
+    pub fn set_sink(&self, sink: Option<DeadLetterSink>) {
+        *self.inner.sink.write().expect("never poisoned") = sink;
+    }
+
+    pub fn stats(&self) -> DeadLetterStats {
+        DeadLetterStats {
+            undelivered: self.inner.undelivered.load(Ordering::Relaxed),
+            forwarded: self.inner.forwarded.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Hands a signal that couldn't be delivered to `recipient` to the sink.
+    ///
+    /// Only messages are dead letters. A process trying to monitor or link to the finished
+    /// `recipient` is notified right away, all other signals are dropped.
+    pub fn push(&self, recipient: Uuid, signal: Signal) {
+        let message = match signal {
+            // A dead letter that can't be delivered means that the sink itself is gone. Forwarding
+            // it again would loop forever.
+            Signal::Message(Message::DeadLetter { .. }) => return,
+            Signal::Message(message) => message,
+            Signal::Monitor(monitor_ref, process) => {
+                let message = Message::ProcessDown {
+                    monitor_ref,
+                    process_id: recipient,
+                    reason: ExitReason::NoProcess,
+                };
+                process.send(Signal::Message(message));
+                return;
+            }
+            Signal::Link(tag, process, _) => {
+                process.send(Signal::LinkDied(recipient, tag, ExitReason::NoProcess));
+                return;
+            }
+            _ => return,
+        };
+        self.inner.undelivered.fetch_add(1, Ordering::Relaxed);
+        // Don't hold the lock while forwarding, the sink could call back into the dead letters.
+        let sink = self.inner.sink.read().expect("never poisoned").clone();
+        match sink {
+            Some(DeadLetterSink::Process(process)) => {
+                self.inner.forwarded.fetch_add(1, Ordering::Relaxed);
+                process.send(Signal::Message(Message::DeadLetter {
+                    recipient,
+                    message: Box::new(message),
+                }));
+            }
+            Some(DeadLetterSink::Callback(callback)) => {
+                self.inner.forwarded.fetch_add(1, Ordering::Relaxed);
+                callback(recipient, message);
+            }
+            None => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use uuid::Uuid;
+
+    use super::{DeadLetterSink, DeadLetterStats, DeadLetters};
+    use crate::{
+        message::Message, spawn, test_helpers::watcher, ExecutionResult, ExitReason, Process,
+        ResultValue, Signal,
+    };
+
+    #[test]
+    fn counts_and_forwards_messages() {
+        let dead_letters = DeadLetters::new();
+        let recipient = Uuid::new_v4();
+        let message = || Signal::Message(Message::LinkDied(Some(1), ExitReason::Normal));
+
+        // Without a sink messages are only counted
+        dead_letters.push(recipient, message());
+        // Other signals are not dead letters
+        dead_letters.push(recipient, Signal::Suspend);
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_ref = received.clone();
+        dead_letters.set_sink(Some(DeadLetterSink::Callback(Arc::new(
+            move |recipient, message: Message| {
+                received_ref
+                    .lock()
+                    .unwrap()
+                    .push((recipient, message.tag()))
+            },
+        ))));
+        dead_letters.push(recipient, message());
+
+        assert_eq!(
+            dead_letters.stats(),
+            DeadLetterStats {
+                undelivered: 2,
+                forwarded: 1
+            }
+        );
+        assert_eq!(*received.lock().unwrap(), vec![(recipient, Some(1))]);
+    }
+
+    #[async_std::test]
+    async fn monitors_of_finished_processes_are_notified() {
+        let (watcher_join, watcher) = watcher();
+        let dead_letters = DeadLetters::new();
+        let recipient = Uuid::new_v4();
+        dead_letters.push(recipient, Signal::Monitor(1, Arc::new(watcher.clone())));
+
+        match watcher_join.await.unwrap() {
+            Message::ProcessDown {
+                monitor_ref,
+                process_id,
+                reason,
+            } => {
+                assert_eq!(monitor_ref, 1);
+                assert_eq!(process_id, recipient);
+                assert_eq!(reason, ExitReason::NoProcess);
+            }
+            _ => panic!("Unexpected message"),
+        }
+        // Monitors are not dead letters
+        assert_eq!(dead_letters.stats().undelivered, 0);
+    }
+
+    #[async_std::test]
+    async fn links_to_finished_processes_are_notified() {
+        let (watcher_join, watcher) = watcher();
+        // Receive the notification as a message instead of dying
+        watcher.send(Signal::DieWhenLinkDies(false));
+        let dead_letters = DeadLetters::new();
+        let watcher_process = Arc::new(watcher.clone());
+        dead_letters.push(
+            Uuid::new_v4(),
+            Signal::Link(Some(7), watcher_process, false),
+        );
+
+        match watcher_join.await.unwrap() {
+            Message::LinkDied(tag, reason) => {
+                assert_eq!(tag, Some(7));
+                assert_eq!(reason, ExitReason::NoProcess);
+            }
+            _ => panic!("Unexpected message"),
+        }
+    }
+
+    #[async_std::test]
+    async fn finished_native_processes_use_the_runtime_dead_letters() {
+        let dead_letters = DeadLetters::new();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        dead_letters.set_sink(Some(DeadLetterSink::Callback(Arc::new(
+            move |recipient, message: Message| {
+                received_clone
+                    .lock()
+                    .unwrap()
+                    .push((recipient, message.tag()))
+            },
+        ))));
+
+        let (join, process) = spawn(dead_letters.clone(), |_, _| async move {
+            ExecutionResult {
+                state: (),
+                result: ResultValue::Ok,
+            }
+        });
+        join.await.unwrap();
+        process.send(Signal::Message(Message::LinkDied(
+            Some(3),
+            ExitReason::Normal,
+        )));
+
+        assert_eq!(*received.lock().unwrap(), vec![(process.id(), Some(3))]);
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use uuid::Uuid;
+
+use crate::Process;
+
+type Members = HashMap<Uuid, Arc<dyn Process>>;
+
+/// Named groups of processes.
+///
+/// A process can be a member of any number of groups and is removed from all of them once it
+/// leaves the [`ProcessTable`](crate::table::ProcessTable). Groups without members are removed.
+#[derive(Clone, Default)]
+pub struct ProcessGroups {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    groups: HashMap<String, Members>,
+    // Names of the groups each process is a member of, so that leaving all of them doesn't require
+    // a walk over every group.
+    memberships: HashMap<Uuid, HashSet<String>>,
+}
+
+impl Debug for ProcessGroups {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let inner = self.inner.lock().expect("never poisoned");
+        f.debug_struct("ProcessGroups")
+            .field("groups", &inner.groups.len())
+            .finish()
+    }
+}
+
+impl ProcessGroups {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the process to the group `name`. Joining a group twice has no effect.
+    pub fn join(&self, name: &str, process: Arc<dyn Process>) {
+        let mut inner = self.inner.lock().expect("never poisoned");
+        inner
+            .memberships
+            .entry(process.id())
+            .or_default()
+            .insert(name.to_owned());
+        inner
+            .groups
+            .entry(name.to_owned())
+            .or_default()
+            .insert(process.id(), process);
+    }
+
+    /// Removes the process with the given ID from the group `name` and returns true if it was a
+    /// member.
+    pub fn leave(&self, name: &str, id: Uuid) -> bool {
+        let mut inner = self.inner.lock().expect("never poisoned");
+        if let Some(names) = inner.memberships.get_mut(&id) {
+            names.remove(name);
+            if names.is_empty() {
+                inner.memberships.remove(&id);
+            }
+        }
+        inner.remove_member(name, id)
+    }
+
+    /// Removes the process with the given ID from all groups.
+    pub fn leave_all(&self, id: Uuid) {
+        let mut inner = self.inner.lock().expect("never poisoned");
+        for name in inner.memberships.remove(&id).unwrap_or_default() {
+            inner.remove_member(&name, id);
+        }
+    }
+
+    /// Returns all members of the group `name`.
+    pub fn members(&self, name: &str) -> Vec<Arc<dyn Process>> {
+        let inner = self.inner.lock().expect("never poisoned");
+        inner
+            .groups
+            .get(name)
+            .map(|members| members.values().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Inner {
+    // Removes the process from the group and the group if it has no members left.
+    fn remove_member(&mut self, name: &str, id: Uuid) -> bool {
+        let members = match self.groups.get_mut(name) {
+            Some(members) => members,
+            None => return false,
+        };
+        let removed = members.remove(&id).is_some();
+        if members.is_empty() {
+            self.groups.remove(name);
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::ProcessGroups;
+    use crate::{test_helpers::idle, Process};
+
+    #[async_std::test]
+    async fn join_and_leave_groups() {
+        let groups = ProcessGroups::new();
+        let mut processes = Vec::new();
+        for _ in 0..2 {
+            let (_, process) = idle();
+            processes.push(process);
+        }
+        groups.join("a", Arc::new(processes[0].clone()));
+        groups.join("a", Arc::new(processes[0].clone()));
+        groups.join("a", Arc::new(processes[1].clone()));
+        groups.join("b", Arc::new(processes[0].clone()));
+
+        assert_eq!(groups.members("a").len(), 2);
+        assert!(groups.leave("a", processes[1].id()));
+        assert!(!groups.leave("a", processes[1].id()));
+        assert_eq!(groups.members("a").len(), 1);
+
+        groups.leave_all(processes[0].id());
+        assert!(groups.members("a").is_empty());
+        assert!(groups.members("b").is_empty());
+        let inner = groups.inner.lock().unwrap();
+        assert!(inner.groups.is_empty());
+        assert!(inner.memberships.is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use uuid::Uuid;
+
+use crate::mailbox::MessageMailbox;
+
+/// What a process is currently doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessStatus {
+    /// The process is executing code or waiting to be scheduled.
+    Running,
+    /// The process is blocked inside of a `receive`, waiting on a message.
+    Waiting,
+    /// The process is blocked inside of a `sleep`.
+    Sleeping,
+    /// The process was suspended and doesn't run until it's resumed.
+    Suspended,
+    /// The process finished and will not run anymore.
+    Finished,
+}
+
+/// A snapshot of a process' runtime information.
+#[derive(Debug, Clone)]
+pub struct ProcessInfo {
+    pub id: Uuid,
+    pub status: ProcessStatus,
+    /// Number of messages waiting in the mailbox.
+    pub mailbox_len: usize,
+    /// Number of late replies that were dropped, because the process stopped waiting on them.
+    pub dropped_replies: u64,
+    /// Size of the linear memory in bytes.
+    pub memory_size: usize,
+    /// Bytes used by the buffers of messages held by the process.
+    pub message_memory: usize,
+    /// Fuel consumed by the process. It's updated every time the process blocks.
+    pub fuel_consumed: u64,
+    /// Number of processes linked to this one.
+    pub links: usize,
+    pub spawned_at: SystemTime,
+    /// ID of the process that spawned this one, if there is one.
+    pub parent: Option<Uuid>,
+    /// IDs of the still running processes spawned by this one.
+    pub children: Vec<Uuid>,
+}
+
+/// The `SharedProcessInfo` collects information about a running process.
+///
+/// It's shared between the process itself, which keeps it up to date, and all handles to the
+/// process, which can take a [`ProcessInfo`] snapshot of it at any time.
+#[derive(Clone)]
+pub struct SharedProcessInfo {
+    id: Uuid,
+    message_mailbox: MessageMailbox,
+    inner: Arc<Mutex<InnerProcessInfo>>,
+}
+
+impl Debug for SharedProcessInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedProcessInfo")
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+struct InnerProcessInfo {
+    status: ProcessStatus,
+    memory_size: usize,
+    fuel_consumed: u64,
+    links: usize,
+    spawned_at: SystemTime,
+    parent: Option<Uuid>,
+    children: Vec<Uuid>,
+}
+
+impl SharedProcessInfo {
+    pub fn new(id: Uuid, message_mailbox: MessageMailbox) -> Self {
+        let inner = InnerProcessInfo {
+            status: ProcessStatus::Running,
+            memory_size: 0,
+            fuel_consumed: 0,
+            links: 0,
+            spawned_at: SystemTime::now(),
+            parent: None,
+            children: Vec::new(),
+        };
+        Self {
+            id,
+            message_mailbox,
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    /// Returns the mailbox of the process.
+    pub fn message_mailbox(&self) -> &MessageMailbox {
+        &self.message_mailbox
+    }
+
+    /// Returns a snapshot of the current process information.
+    pub fn snapshot(&self) -> ProcessInfo {
+        let inner = self.inner.lock().expect("never poisoned");
+        ProcessInfo {
+            id: self.id,
+            status: inner.status,
+            mailbox_len: self.message_mailbox.len(),
+            dropped_replies: self.message_mailbox.dropped_replies(),
+            memory_size: inner.memory_size,
+            message_memory: self.message_mailbox.memory().used(),
+            fuel_consumed: inner.fuel_consumed,
+            links: inner.links,
+            spawned_at: inner.spawned_at,
+            parent: inner.parent,
+            children: inner.children.clone(),
+        }
+    }
+
+    pub fn status(&self) -> ProcessStatus {
+        self.inner.lock().expect("never poisoned").status
+    }
+
+    pub fn set_status(&self, status: ProcessStatus) {
+        self.inner.lock().expect("never poisoned").status = status;
+    }
+
+    pub fn set_memory_size(&self, memory_size: usize) {
+        self.inner.lock().expect("never poisoned").memory_size = memory_size;
+    }
+
+    pub fn set_fuel_consumed(&self, fuel_consumed: u64) {
+        self.inner.lock().expect("never poisoned").fuel_consumed = fuel_consumed;
+    }
+
+    pub fn set_links(&self, links: usize) {
+        self.inner.lock().expect("never poisoned").links = links;
+    }
+
+    pub fn set_parent(&self, parent: Option<Uuid>) {
+        self.inner.lock().expect("never poisoned").parent = parent;
+    }
+
+    pub fn add_child(&self, child: Uuid) {
+        self.inner
+            .lock()
+            .expect("never poisoned")
+            .children
+            .push(child);
+    }
+
+    pub fn remove_child(&self, child: Uuid) {
+        let mut inner = self.inner.lock().expect("never poisoned");
+        inner.children.retain(|id| *id != child);
+    }
+}
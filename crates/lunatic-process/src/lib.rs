@@ -1,21 +1,47 @@
 pub mod config;
+pub mod dead_letters;
+pub mod groups;
+pub mod info;
 pub mod mailbox;
 pub mod message;
 pub mod runtimes;
 pub mod state;
+pub mod table;
 pub mod wasm;
 
-use std::{collections::HashMap, fmt::Debug, future::Future, hash::Hash, sync::Arc};
+#[cfg(test)]
+mod test_helpers;
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt::{Debug, Display},
+    future::Future,
+    hash::Hash,
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc,
+    },
+    task::Poll,
+    time::{Duration, Instant},
+};
 
 use anyhow::{anyhow, Result};
 use log::{debug, log_enabled, trace, warn, Level};
 
 use async_std::channel::{unbounded, Receiver, Sender};
 use async_std::task::JoinHandle;
+use futures::FutureExt;
 
 use uuid::Uuid;
 
-use crate::{mailbox::MessageMailbox, message::Message};
+use crate::{
+    dead_letters::DeadLetters,
+    info::{ProcessInfo, ProcessStatus, SharedProcessInfo},
+    mailbox::{MessageMailbox, OverflowPolicy},
+    message::Message,
+};
 
 /// The `Process` is the main abstraction in lunatic.
 ///
@@ -28,6 +54,10 @@ use crate::{mailbox::MessageMailbox, message::Message};
 pub trait Process: Send + Sync {
     fn id(&self) -> Uuid;
     fn send(&self, signal: Signal);
+    /// Returns a snapshot of the process' runtime information (status, mailbox length, ...).
+    fn info(&self) -> ProcessInfo;
+    /// Returns true if the process' mailbox is full and senders should back off.
+    fn back_pressure(&self) -> bool;
 }
 
 impl Debug for dyn Process {
@@ -54,43 +84,184 @@ impl Hash for dyn Process {
 pub enum Signal {
     // Messages can contain opaque data.
     Message(Message),
-    // When received, the process should stop immediately.
-    Kill,
+    // When received, the process should stop immediately. Contains the ID of the process that
+    // requested the kill and the reason given by it, if there are any.
+    Kill(Option<Uuid>, Option<i64>),
+    // Request for the process to shut down gracefully. It's turned into a `Message::Shutdown`, so
+    // that the process can clean up. If the process didn't finish after `timeout` it's killed.
+    Shutdown { timeout: Duration },
     // Change behaviour of what happens if a linked process dies.
     DieWhenLinkDies(bool),
     // Sent from a process that wants to be linked. In case of a death the tag will be returned
-    // to the sender in form of a `LinkDied` signal.
-    Link(Option<i64>, Arc<dyn Process>),
+    // to the sender in form of a `LinkDied` signal. If the last value is `true`, the sender is
+    // also notified if the process finishes normally.
+    Link(Option<i64>, Arc<dyn Process>, bool),
     // Request from a process to be unlinked
     UnLink(Arc<dyn Process>),
-    // Sent to linked processes when the link dies. Contains the tag used when the link was
-    // established. Depending on the value of `die_when_link_dies` (default is `true`) this
-    // receiving process will turn this signal into a message or the process will immediately
-    // die as well.
-    LinkDied(Option<i64>),
+    // Sent to linked processes when the link dies. Contains the ID of the dead process, the tag
+    // used when the link was established and the reason of the death. Depending on the value of
+    // `die_when_link_dies` (default is `true`) this receiving process will turn this signal into a
+    // message or the process will immediately die as well.
+    LinkDied(Uuid, Option<i64>, ExitReason),
+    // Sent from a process that wants to monitor this one. In case of a death a
+    // `Message::ProcessDown` containing the monitor reference is sent to the monitoring process.
+    // Unlike links, monitors are one-way and never kill the monitoring process.
+    Monitor(u64, Arc<dyn Process>),
+    // Request from a process to remove the monitor with the given reference.
+    Demonitor(u64),
+    // Stop running the process until a `Resume` signal is received. A suspended process still
+    // receives messages and handles signals, e.g. it can be killed.
+    Suspend,
+    // Continue running a suspended process.
+    Resume,
+    // Sent to the parent when it spawns a new process. Contains the ID of the child.
+    ChildSpawned(Uuid),
+    // Sent to the parent when one of its children finishes. Contains the ID of the child.
+    ChildFinished(Uuid),
 }
 
 impl Debug for Signal {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Message(_) => write!(f, "Message"),
-            Self::Kill => write!(f, "Kill"),
+            Self::Kill(_, _) => write!(f, "Kill"),
+            Self::Shutdown { .. } => write!(f, "Shutdown"),
             Self::DieWhenLinkDies(_) => write!(f, "DieWhenLinkDies"),
-            Self::Link(_, _) => write!(f, "Link"),
+            Self::Link(_, _, _) => write!(f, "Link"),
             Self::UnLink(_) => write!(f, "UnLink"),
-            Self::LinkDied(_) => write!(f, "LinkDied"),
+            Self::LinkDied(_, _, _) => write!(f, "LinkDied"),
+            Self::Monitor(_, _) => write!(f, "Monitor"),
+            Self::Demonitor(_) => write!(f, "Demonitor"),
+            Self::Suspend => write!(f, "Suspend"),
+            Self::Resume => write!(f, "Resume"),
+            Self::ChildSpawned(_) => write!(f, "ChildSpawned"),
+            Self::ChildFinished(_) => write!(f, "ChildFinished"),
         }
     }
 }
 
+// Monitor references are unique across all processes of a runtime.
+static NEXT_MONITOR_REF: AtomicU64 = AtomicU64::new(1);
+
+/// Returns a new monitor reference that is unique inside the runtime.
+///
+/// It's used to match `Message::ProcessDown` messages to the `Signal::Monitor` that created them.
+pub fn new_monitor_ref() -> u64 {
+    NEXT_MONITOR_REF.fetch_add(1, Ordering::Relaxed)
+}
+
+// References are unique across all processes of a runtime.
+static NEXT_REF: AtomicI64 = AtomicI64::new(1);
+
+/// Returns a new reference that is unique inside the runtime.
+///
+/// References are used as message tags to match replies to requests. They are always negative and
+/// never repeat, but guests can also pick negative tags (e.g. when replying to a request), so they
+/// are only guaranteed to be different from other references.
+pub fn new_ref() -> i64 {
+    -NEXT_REF.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Returns true if the `value` was returned by [`new_ref`].
+pub fn is_ref(value: i64) -> bool {
+    value < 0 && value > -NEXT_REF.load(Ordering::Relaxed)
+}
+
 /// The reason of a process finishing
 pub enum Finished<T> {
     /// This just means that the process finished without external interaction.
     /// In case of Wasm this could mean that the entry function returned normally or that it
     /// **trapped**.
     Normal(T),
-    /// The process was terminated by an external `Kill` signal.
-    KillSignal,
+    /// The process was terminated by an external `Kill` signal or the death of a link. Contains
+    /// the ID of the process that caused the kill and the kill reason, if there are any.
+    KillSignal(Option<Uuid>, Option<i64>),
+    /// A host function panicked while the process was running. Contains the panic message.
+    Panic(String),
+    /// A message arrived at the full mailbox of a process with the `KillReceiver` overflow policy.
+    MailboxOverflow,
+    /// The process was still running after its maximum execution time.
+    ExecutionTimeLimit,
+    /// The process ran longer than its maximum CPU time without yielding.
+    CpuTimeLimit,
+    /// The process didn't finish in time after it was asked to shut down.
+    ShutdownTimeout,
+}
+
+/// The reason of a process finishing, as seen by linked and monitoring processes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExitReason {
+    /// The entry function returned normally.
+    Normal,
+    /// The process trapped. Contains the trap message.
+    Trapped(String),
+    /// The process was killed by a `Kill` signal or the death of a link. Contains the ID of the
+    /// process that caused the kill and the reason passed to `kill`, if there are any.
+    Killed {
+        by: Option<Uuid>,
+        reason: Option<i64>,
+    },
+    /// The process used up all of its fuel.
+    FuelExhausted,
+    /// The process failed after reaching its memory limit.
+    MemoryLimit,
+    /// The process could not be started, e.g. the entry function doesn't exist.
+    SpawnError(String),
+    /// A host function panicked while the process was running. Contains the panic message.
+    Panicked(String),
+    /// The mailbox overflowed and the process was killed because of its overflow policy.
+    MailboxOverflow,
+    /// The process was killed, because it was still running after its maximum execution time.
+    ExecutionTimeLimit,
+    /// The process was killed, because it ran longer than its maximum CPU time without yielding.
+    CpuTimeLimit,
+    /// The process already finished when it was linked or monitored.
+    NoProcess,
+    /// The process was killed, because it didn't finish in time after it was asked to shut down.
+    ShutdownTimeout,
+}
+
+impl ExitReason {
+    /// Returns true if the process finished without a failure.
+    pub fn is_normal(&self) -> bool {
+        matches!(self, ExitReason::Normal)
+    }
+}
+
+impl Display for ExitReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExitReason::Normal => write!(f, "Process finished normally"),
+            ExitReason::Trapped(trap) => write!(f, "Process trapped: {}", trap),
+            ExitReason::Killed {
+                by: Some(id),
+                reason: Some(reason),
+            } => write!(f, "Process was killed by {} with reason {}", id, reason),
+            ExitReason::Killed {
+                by: Some(id),
+                reason: None,
+            } => write!(f, "Process was killed by {}", id),
+            ExitReason::Killed {
+                by: None,
+                reason: Some(reason),
+            } => write!(f, "Process was killed with reason {}", reason),
+            ExitReason::Killed {
+                by: None,
+                reason: None,
+            } => write!(f, "Process was killed"),
+            ExitReason::FuelExhausted => write!(f, "Process used up all of its fuel"),
+            ExitReason::MemoryLimit => write!(f, "Process reached its memory limit"),
+            ExitReason::SpawnError(error) => write!(f, "Process failed to spawn: {}", error),
+            ExitReason::Panicked(panic) => write!(f, "Process panicked: {}", panic),
+            ExitReason::MailboxOverflow => write!(f, "Process mailbox overflowed"),
+            ExitReason::ExecutionTimeLimit => {
+                write!(f, "Process reached its execution time limit")
+            }
+            ExitReason::CpuTimeLimit => write!(f, "Process reached its CPU time limit"),
+            ExitReason::NoProcess => write!(f, "Process doesn't exist"),
+            ExitReason::ShutdownTimeout => write!(f, "Process didn't shut down in time"),
+        }
+    }
 }
 
 /// A `WasmProcess` represents an instance of a Wasm module that is being executed.
@@ -101,12 +272,24 @@ pub enum Finished<T> {
 pub struct WasmProcess {
     id: Uuid,
     signal_mailbox: Sender<Signal>,
+    info: SharedProcessInfo,
+    dead_letters: DeadLetters,
 }
 
 impl WasmProcess {
     /// Create a new WasmProcess
-    pub fn new(id: Uuid, signal_mailbox: Sender<Signal>) -> Self {
-        Self { id, signal_mailbox }
+    pub fn new(
+        id: Uuid,
+        signal_mailbox: Sender<Signal>,
+        info: SharedProcessInfo,
+        dead_letters: DeadLetters,
+    ) -> Self {
+        Self {
+            id,
+            signal_mailbox,
+            info,
+            dead_letters,
+        }
     }
 }
 
@@ -115,14 +298,35 @@ impl Process for WasmProcess {
         self.id
     }
     fn send(&self, signal: Signal) {
-        // If the receiver doesn't exist or is closed, just ignore it and drop the `signal`.
+        // If the receiver doesn't exist or is closed, hand the `signal` to the dead letters.
         // lunatic can't guarantee that a message was successfully seen by the receiving side even
         // if this call succeeds. We deliberately don't expose this API, as it would not make sense
         // to relay on it and could signal wrong guarantees to users.
-        let _ = self.signal_mailbox.try_send(signal);
+        if let Err(err) = self.signal_mailbox.try_send(signal) {
+            self.dead_letters.push(self.id, err.into_inner());
+        }
+    }
+    fn info(&self) -> ProcessInfo {
+        self.info.snapshot()
+    }
+    fn back_pressure(&self) -> bool {
+        // Messages still waiting in the signal queue will also end up in the mailbox.
+        let pending = self.signal_mailbox.len();
+        self.info.message_mailbox().back_pressure(pending)
     }
 }
 
+/// Limits on how long a process can run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeLimits {
+    /// Wall-clock time since the process was spawned, including the time it's blocked, sleeping
+    /// or suspended.
+    pub execution_time: Option<Duration>,
+    /// Time the process can run between two yields. It's measured for each slice on its own, so
+    /// that long-lived processes are not limited in total.
+    pub cpu_time: Option<Duration>,
+}
+
 /// Turns a `Future` into a process, enabling signals (e.g. kill).
 ///
 /// This function represents the core execution loop of lunatic processes:
@@ -134,7 +338,8 @@ impl Process for WasmProcess {
 /// computation.
 ///
 /// The `Future` is in charge to periodically yield back the execution with `Poll::Pending` to give
-/// the signal handler a chance to run and process pending signals.
+/// the signal handler a chance to run and process pending signals. The `time_limits` are only
+/// checked at this points, too.
 ///
 /// In case of success, the process state `S` is returned. It's not possible to return the process
 /// state in case of failure because of limitations in the Wasmtime API:
@@ -144,47 +349,129 @@ pub(crate) async fn new<F, S>(
     id: Uuid,
     signal_mailbox: Receiver<Signal>,
     message_mailbox: MessageMailbox,
+    info: SharedProcessInfo,
+    dead_letters: DeadLetters,
+    time_limits: TimeLimits,
 ) -> Result<S>
 where
     F: Future<Output = ExecutionResult<S>> + Send + 'static,
 {
     trace!("Process {} spawned", id);
+    // A panic inside of a host function unwinds through the Wasm code and is caught here, so that
+    // it can be reported as a process failure and linked processes get notified.
+    let fut = AssertUnwindSafe(fut).catch_unwind();
     tokio::pin!(fut);
+    // Measure the time spent in each poll of the future, the time between two yields. It resolves
+    // to `None` once a single poll runs longer than the CPU time limit.
+    let mut fut = futures::future::poll_fn(|cx| {
+        let start = Instant::now();
+        let poll = fut.as_mut().poll(cx);
+        let cpu_time = start.elapsed();
+        match poll {
+            Poll::Pending if time_limits.cpu_time.is_some_and(|max| cpu_time > max) => {
+                Poll::Ready(None)
+            }
+            poll => poll.map(Some),
+        }
+    });
+    // The process is killed if it's still running at this point in time. A limit too large to
+    // represent never expires.
+    let execution_deadline = time_limits
+        .execution_time
+        .and_then(|execution_time| Instant::now().checked_add(execution_time));
 
     // Defines what happens if one of the linked processes dies.
     // If the value is set to false, instead of dying too the process will receive a message about
     // the linked process' death.
     let mut die_when_link_dies = true;
-    // Process linked to this one
+    // Process linked to this one, with the link tag and if they should be notified about a
+    // normal exit
     let mut links = HashMap::new();
-    // TODO: Maybe wrapping this in some kind of `std::panic::catch_unwind` wold be a good idea,
-    //       to protect against panics in host function calls that unwind through Wasm code.
-    //       Currently a panic would just kill the task, but not notify linked processes.
+    // Processes monitoring this one, indexed by the monitor reference
+    let mut monitors = HashMap::new();
+    // If a shutdown was requested, the process will be killed after this point in time
+    let mut shutdown_deadline: Option<Instant> = None;
+    // A suspended process doesn't poll the future. Contains the status before the suspension.
+    let mut suspended: Option<ProcessStatus> = None;
     let result = loop {
+        info.set_links(links.len());
+        let until_shutdown = shutdown_deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+            .unwrap_or_default();
+        let until_deadline = execution_deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+            .unwrap_or_default();
         tokio::select! {
             biased;
             // Handle signals first
             signal = signal_mailbox.recv() => {
                 match signal {
-                    Ok(Signal::Message(message)) => message_mailbox.push(message),
+                    Ok(Signal::Message(message)) => match message_mailbox.try_push(message) {
+                        Ok(accepted) => {
+                            let policy = message_mailbox.overflow_policy();
+                            if !accepted && policy == OverflowPolicy::KillReceiver {
+                                break Finished::MailboxOverflow
+                            }
+                        },
+                        // The process can't hold on to the message without exceeding its memory
+                        // limit.
+                        Err(message) => dead_letters.push(id, Signal::Message(*message)),
+                    },
                     Ok(Signal::DieWhenLinkDies(value)) => die_when_link_dies = value,
                     // Put process into list of linked processes
-                    Ok(Signal::Link(tag, proc)) => { links.insert(proc, tag); },
+                    Ok(Signal::Link(tag, proc, notify_normal_exit)) => {
+                        links.insert(proc, (tag, notify_normal_exit));
+                    },
                     // Remove process from list
                     Ok(Signal::UnLink(proc)) => { links.remove(&proc); }
+                    // Put process into list of monitoring processes
+                    Ok(Signal::Monitor(monitor_ref, proc)) => {
+                        monitors.insert(monitor_ref, proc);
+                    },
+                    // Remove monitor from list
+                    Ok(Signal::Demonitor(monitor_ref)) => { monitors.remove(&monitor_ref); }
+                    // Stop polling the future until resumed
+                    Ok(Signal::Suspend) => {
+                        if suspended.is_none() {
+                            suspended = Some(info.status());
+                            info.set_status(ProcessStatus::Suspended);
+                        }
+                    },
+                    Ok(Signal::Resume) => {
+                        if let Some(status) = suspended.take() {
+                            info.set_status(status);
+                        }
+                    },
+                    // Keep track of the process tree
+                    Ok(Signal::ChildSpawned(child)) => info.add_child(child),
+                    Ok(Signal::ChildFinished(child)) => info.remove_child(child),
                     // Exit loop and don't poll anymore the future if Signal::Kill received.
-                    Ok(Signal::Kill) => break Finished::KillSignal,
+                    Ok(Signal::Kill(killed_by, reason)) => {
+                        break Finished::KillSignal(killed_by, reason)
+                    },
+                    // Let the process know that it should finish and start the shutdown timer.
+                    Ok(Signal::Shutdown { timeout }) => {
+                        message_mailbox.push(Message::Shutdown);
+                        // A timeout too large to represent never expires
+                        if let Some(deadline) = Instant::now().checked_add(timeout) {
+                            // Repeated requests can only shorten the time left
+                            let deadline = shutdown_deadline.map_or(deadline, |d| d.min(deadline));
+                            shutdown_deadline = Some(deadline);
+                        }
+                    },
                     // Depending if `die_when_link_dies` is set, process will die or turn the
                     // signal into a message
-                    Ok(Signal::LinkDied(tag)) => {
-                        if die_when_link_dies {
+                    Ok(Signal::LinkDied(link_id, tag, reason)) => {
+                        // Remove the dead process from our notify list, so we don't send back
+                        // the same notification to an already dead process.
+                        links.retain(|proc: &Arc<dyn Process>, _| proc.id() != link_id);
+                        // A link finishing normally never takes this process down with it.
+                        if die_when_link_dies && !reason.is_normal() {
                             // Even this was not a **kill** signal it has the same effect on
                             // this process and should be propagated as such.
-                            // TODO: Remove sender from our notify list, so we don't send back the
-                            //       same notification to an already dead process.
-                            break Finished::KillSignal
+                            break Finished::KillSignal(Some(link_id), None)
                         } else {
-                            let message = Message::LinkDied(tag);
+                            let message = Message::LinkDied(tag, reason);
                             message_mailbox.push(message);
                         }
                     },
@@ -192,9 +479,49 @@ where
                 }
             }
             // Run process
-            output = &mut fut => { break Finished::Normal(output); }
+            output = &mut fut, if suspended.is_none() => {
+                match output {
+                    Some(Ok(output)) => break Finished::Normal(output),
+                    Some(Err(panic)) => break Finished::Panic(panic_message(panic)),
+                    None => break Finished::CpuTimeLimit,
+                }
+            }
+            // Kill the process if it didn't finish in time after a shutdown request
+            _ = async_std::task::sleep(until_shutdown), if shutdown_deadline.is_some() => {
+                break Finished::ShutdownTimeout
+            }
+            // Kill the process if it's still running after the maximum execution time
+            _ = async_std::task::sleep(until_deadline), if execution_deadline.is_some() => {
+                break Finished::ExecutionTimeLimit
+            }
         }
     };
+    info.set_status(ProcessStatus::Finished);
+    // Messages that were never received are dropped with their resources, even if handles to the
+    // process still exist.
+    message_mailbox.clear();
+    // Signals sent from now on can't be handled anymore and end up in the dead letters.
+    signal_mailbox.close();
+    // The same is true for signals that were already queued, e.g. a monitor established right
+    // before the process finished.
+    while let Ok(signal) = signal_mailbox.try_recv() {
+        dead_letters.push(id, signal);
+    }
+    let exit_reason = match &result {
+        Finished::Normal(result) => result.exit_reason(),
+        Finished::KillSignal(by, reason) => ExitReason::Killed {
+            by: *by,
+            reason: *reason,
+        },
+        Finished::Panic(panic) => ExitReason::Panicked(panic.clone()),
+        Finished::MailboxOverflow => ExitReason::MailboxOverflow,
+        Finished::ExecutionTimeLimit => ExitReason::ExecutionTimeLimit,
+        Finished::CpuTimeLimit => ExitReason::CpuTimeLimit,
+        Finished::ShutdownTimeout => ExitReason::ShutdownTimeout,
+    };
+    // Links are only notified about a normal exit if they requested it
+    notify_links(id, &links, &exit_reason);
+    notify_monitors(id, &monitors, &exit_reason);
     match result {
         Finished::Normal(result) => {
             if let Some(failure) = result.failure() {
@@ -210,49 +537,136 @@ where
                     }
                 );
                 debug!("{}", failure);
-                // Notify all links that we finished with an error
-                links.iter().for_each(|(proc, tag)| {
-                    proc.send(Signal::LinkDied(*tag));
-                });
                 Err(anyhow!(failure.to_string()))
             } else {
                 Ok(result.state())
             }
         }
-        Finished::KillSignal => {
+        Finished::KillSignal(_, _) => {
             warn!(
                 "Process {} was killed, notifying: {} links",
                 id,
                 links.len()
             );
-            // Notify all links that we finished because of a kill signal
-            links.iter().for_each(|(proc, tag)| {
-                proc.send(Signal::LinkDied(*tag));
-            });
-            Err(anyhow!("Process received Kill signal"))
+            Err(anyhow!(exit_reason.to_string()))
+        }
+        Finished::Panic(panic) => {
+            warn!(
+                "Process {} panicked inside a host function, notifying: {} links",
+                id,
+                links.len()
+            );
+            debug!("{}", panic);
+            Err(anyhow!(exit_reason.to_string()))
+        }
+        Finished::MailboxOverflow => {
+            warn!(
+                "Process {} was killed because its mailbox overflowed, notifying: {} links",
+                id,
+                links.len()
+            );
+            Err(anyhow!(exit_reason.to_string()))
+        }
+        Finished::ExecutionTimeLimit | Finished::CpuTimeLimit => {
+            warn!(
+                "Process {} was killed because it reached its time limit, notifying: {} links",
+                id,
+                links.len()
+            );
+            Err(anyhow!(exit_reason.to_string()))
+        }
+        Finished::ShutdownTimeout => {
+            warn!(
+                "Process {} was killed because it didn't shut down in time, notifying: {} links",
+                id,
+                links.len()
+            );
+            Err(anyhow!(exit_reason.to_string()))
         }
     }
 }
 
+// Returns the message of a caught panic.
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic payload".to_string()
+    }
+}
+
+// Send a `Signal::LinkDied` to all processes linked to the process `id`.
+//
+// In case of a normal exit only links that requested it are notified.
+fn notify_links(
+    id: Uuid,
+    links: &HashMap<Arc<dyn Process>, (Option<i64>, bool)>,
+    reason: &ExitReason,
+) {
+    links
+        .iter()
+        .filter(|(_, (_, notify_normal_exit))| *notify_normal_exit || !reason.is_normal())
+        .for_each(|(proc, (tag, _))| {
+            proc.send(Signal::LinkDied(id, *tag, reason.clone()));
+        });
+}
+
+// Send a `Message::ProcessDown` to all processes monitoring the process `id`.
+//
+// Monitors are notified on every exit, including normal ones.
+fn notify_monitors(id: Uuid, monitors: &HashMap<u64, Arc<dyn Process>>, reason: &ExitReason) {
+    monitors.iter().for_each(|(monitor_ref, proc)| {
+        let message = Message::ProcessDown {
+            monitor_ref: *monitor_ref,
+            process_id: id,
+            reason: reason.clone(),
+        };
+        proc.send(Signal::Message(message));
+    });
+}
+
 /// A process spawned from a native Rust closure.
 #[derive(Clone, Debug)]
 pub struct NativeProcess {
     id: Uuid,
     signal_mailbox: Sender<Signal>,
+    info: SharedProcessInfo,
+    dead_letters: DeadLetters,
 }
 
 /// Spawns a process from a closure.
 ///
+/// Signals sent to the process after it finished are handed to `dead_letters`, which should be the
+/// [`DeadLetters`] of the runtime the process is part of.
+///
 /// ## Example:
 ///
 /// ```no_run
-/// let _proc = lunatic_runtime::spawn(|_this, mailbox| async move {
+/// let dead_letters = lunatic_runtime::DeadLetters::new();
+/// let _proc = lunatic_runtime::spawn(dead_letters, |_this, mailbox| async move {
 ///     // Wait on a message with the tag `27`.
 ///     mailbox.pop(Some(&[27])).await;
 ///     Ok(())
 /// });
 /// ```
-pub fn spawn<T, F, K>(func: F) -> (JoinHandle<Result<T>>, NativeProcess)
+pub fn spawn<T, F, K>(dead_letters: DeadLetters, func: F) -> (JoinHandle<Result<T>>, NativeProcess)
+where
+    T: Send + 'static,
+    K: Future<Output = ExecutionResult<T>> + Send + 'static,
+    F: FnOnce(NativeProcess, MessageMailbox) -> K,
+{
+    spawn_with_limits(dead_letters, TimeLimits::default(), func)
+}
+
+/// Spawns a process from a closure, like [`spawn`], that is killed once it reaches one of the
+/// `time_limits`.
+pub(crate) fn spawn_with_limits<T, F, K>(
+    dead_letters: DeadLetters,
+    time_limits: TimeLimits,
+    func: F,
+) -> (JoinHandle<Result<T>>, NativeProcess)
 where
     T: Send + 'static,
     K: Future<Output = ExecutionResult<T>> + Send + 'static,
@@ -262,12 +676,23 @@ where
     let id = Uuid::new_v4();
     let (signal_sender, signal_mailbox) = unbounded::<Signal>();
     let message_mailbox = MessageMailbox::default();
+    let info = SharedProcessInfo::new(id, message_mailbox.clone());
     let process = NativeProcess {
         id,
         signal_mailbox: signal_sender,
+        info: info.clone(),
+        dead_letters,
     };
     let fut = func(process.clone(), message_mailbox.clone());
-    let join = async_std::task::spawn(new(fut, id, signal_mailbox, message_mailbox));
+    let join = async_std::task::spawn(new(
+        fut,
+        id,
+        signal_mailbox,
+        message_mailbox,
+        info,
+        process.dead_letters.clone(),
+        time_limits,
+    ));
     (join, process)
 }
 
@@ -276,11 +701,21 @@ impl Process for NativeProcess {
         self.id
     }
     fn send(&self, signal: Signal) {
-        // If the receiver doesn't exist or is closed, just ignore it and drop the `signal`.
+        // If the receiver doesn't exist or is closed, hand the `signal` to the dead letters.
         // lunatic can't guarantee that a message was successfully seen by the receiving side even
         // if this call succeeds. We deliberately don't expose this API, as it would not make sense
         // to relay on it and could signal wrong guarantees to users.
-        let _ = self.signal_mailbox.try_send(signal);
+        if let Err(err) = self.signal_mailbox.try_send(signal) {
+            self.dead_letters.push(self.id, err.into_inner());
+        }
+    }
+    fn info(&self) -> ProcessInfo {
+        self.info.snapshot()
+    }
+    fn back_pressure(&self) -> bool {
+        // Messages still waiting in the signal queue will also end up in the mailbox.
+        let pending = self.signal_mailbox.len();
+        self.info.message_mailbox().back_pressure(pending)
     }
 }
 
@@ -297,11 +732,24 @@ impl<T> ExecutionResult<T> {
     pub fn failure(&self) -> Option<&str> {
         match self.result {
             ResultValue::Failed(ref failure) => Some(failure),
+            ResultValue::FuelExhausted(ref failure) => Some(failure),
+            ResultValue::MemoryLimit(ref failure) => Some(failure),
             ResultValue::SpawnError(ref failure) => Some(failure),
             _ => None,
         }
     }
 
+    // Returns the reason of the process finishing.
+    pub fn exit_reason(&self) -> ExitReason {
+        match self.result {
+            ResultValue::Ok => ExitReason::Normal,
+            ResultValue::Failed(ref failure) => ExitReason::Trapped(failure.clone()),
+            ResultValue::FuelExhausted(_) => ExitReason::FuelExhausted,
+            ResultValue::MemoryLimit(_) => ExitReason::MemoryLimit,
+            ResultValue::SpawnError(ref failure) => ExitReason::SpawnError(failure.clone()),
+        }
+    }
+
     // Returns the process state
     pub fn state(self) -> T {
         self.state
@@ -312,5 +760,289 @@ impl<T> ExecutionResult<T> {
 pub enum ResultValue {
     Ok,
     Failed(String),
+    FuelExhausted(String),
+    MemoryLimit(String),
     SpawnError(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::dead_letters::DeadLetters;
+    use crate::info::ProcessStatus;
+    use crate::message::{DataMessage, Message};
+    use crate::test_helpers::{idle, watcher};
+    use crate::{
+        is_ref, new_ref, spawn, spawn_with_limits, ExecutionResult, ExitReason, Process,
+        ResultValue, Signal, TimeLimits,
+    };
+
+    #[test]
+    fn only_issued_references_are_recognized() {
+        let reference = new_ref();
+        assert!(is_ref(reference));
+        assert!(!is_ref(reference - 1_000_000));
+        assert!(!is_ref(0));
+        assert!(!is_ref(-reference));
+        assert!(!is_ref(i64::MIN));
+    }
+
+    #[async_std::test]
+    async fn panic_is_reported_to_monitors() {
+        let (watcher_join, watcher) = watcher();
+        let (join, process) = spawn(DeadLetters::default(), |_, mailbox| async move {
+            // Wait until the monitor is established
+            mailbox.pop(None).await;
+            panic!("host function panicked");
+            #[allow(unreachable_code)]
+            ExecutionResult {
+                state: (),
+                result: ResultValue::Ok,
+            }
+        });
+        process.send(Signal::Monitor(1, Arc::new(watcher.clone())));
+        process.send(Signal::Message(Message::Data(DataMessage::new(None, 0))));
+
+        assert!(join.await.is_err());
+        match watcher_join.await.unwrap() {
+            Message::ProcessDown {
+                monitor_ref,
+                process_id,
+                reason,
+            } => {
+                assert_eq!(monitor_ref, 1);
+                assert_eq!(process_id, process.id());
+                assert_eq!(
+                    reason,
+                    ExitReason::Panicked("host function panicked".to_string())
+                );
+            }
+            _ => panic!("Unexpected message"),
+        }
+    }
+
+    #[async_std::test]
+    async fn monitoring_a_finished_process() {
+        let (watcher_join, watcher) = watcher();
+        let (join, process) = spawn(DeadLetters::default(), |_, _| async move {
+            ExecutionResult {
+                state: (),
+                result: ResultValue::Ok,
+            }
+        });
+        assert!(join.await.is_ok());
+        process.send(Signal::Monitor(1, Arc::new(watcher.clone())));
+
+        match watcher_join.await.unwrap() {
+            Message::ProcessDown { reason, .. } => assert_eq!(reason, ExitReason::NoProcess),
+            _ => panic!("Unexpected message"),
+        }
+    }
+
+    #[async_std::test]
+    async fn shutdown_escalates_to_kill() {
+        let (watcher_join, watcher) = watcher();
+        let (join, process) = spawn(DeadLetters::default(), |_, mailbox| async move {
+            // Receive the shutdown request, but never finish
+            let message = mailbox.pop(None).await;
+            assert!(matches!(message, Message::Shutdown));
+            futures::future::pending::<()>().await;
+            ExecutionResult {
+                state: (),
+                result: ResultValue::Ok,
+            }
+        });
+        process.send(Signal::Monitor(1, Arc::new(watcher.clone())));
+        process.send(Signal::Shutdown {
+            timeout: Duration::from_millis(10),
+        });
+
+        assert!(join.await.is_err());
+        match watcher_join.await.unwrap() {
+            Message::ProcessDown { reason, .. } => {
+                assert_eq!(reason, ExitReason::ShutdownTimeout)
+            }
+            _ => panic!("Unexpected message"),
+        }
+    }
+
+    #[async_std::test]
+    async fn info_reflects_process_state() {
+        let (_, other) = idle();
+        let (join, process) = spawn(DeadLetters::default(), |_, mailbox| async move {
+            // Wait on a message that never arrives
+            mailbox.pop(Some(&[1])).await;
+            ExecutionResult {
+                state: (),
+                result: ResultValue::Ok,
+            }
+        });
+        process.send(Signal::Message(Message::Data(DataMessage::new(None, 0))));
+        process.send(Signal::Message(Message::Data(DataMessage::new(None, 0))));
+        process.send(Signal::Link(None, Arc::new(other.clone()), false));
+        // Signals are handled in order, wait until the last one is processed
+        for _ in 0..100 {
+            if process.info().links == 1 {
+                break;
+            }
+            async_std::task::sleep(Duration::from_millis(1)).await;
+        }
+
+        let info = process.info();
+        assert_eq!(info.id, process.id());
+        assert_eq!(info.status, ProcessStatus::Running);
+        assert_eq!(info.mailbox_len, 2);
+        assert_eq!(info.links, 1);
+
+        process.send(Signal::Kill(None, None));
+        assert!(join.await.is_err());
+        let info = process.info();
+        assert_eq!(info.status, ProcessStatus::Finished);
+        // The handle doesn't keep messages of the finished process alive
+        assert_eq!(info.mailbox_len, 0);
+    }
+
+    #[async_std::test]
+    async fn info_tracks_children() {
+        let (_, process) = idle();
+        let first = uuid::Uuid::new_v4();
+        let second = uuid::Uuid::new_v4();
+        process.send(Signal::ChildSpawned(first));
+        process.send(Signal::ChildSpawned(second));
+        process.send(Signal::ChildFinished(first));
+        // Signals are handled in order, wait until the last one is processed
+        for _ in 0..100 {
+            if process.info().children == vec![second] {
+                break;
+            }
+            async_std::task::sleep(Duration::from_millis(1)).await;
+        }
+
+        assert_eq!(process.info().children, vec![second]);
+    }
+
+    #[async_std::test]
+    async fn suspended_process_does_not_run() {
+        let (join, process) = idle();
+        process.send(Signal::Suspend);
+        process.send(Signal::Message(Message::Data(DataMessage::new(None, 0))));
+        // Signals are handled in order, wait until the last one is processed
+        for _ in 0..100 {
+            if process.info().mailbox_len == 1 {
+                break;
+            }
+            async_std::task::sleep(Duration::from_millis(1)).await;
+        }
+
+        // The message is queued, but the process can't finish while suspended
+        let info = process.info();
+        assert_eq!(info.status, ProcessStatus::Suspended);
+        assert_eq!(info.mailbox_len, 1);
+
+        process.send(Signal::Resume);
+        assert!(join.await.is_ok());
+    }
+
+    #[async_std::test]
+    async fn execution_time_limit_kills_process() {
+        let (watcher_join, watcher) = watcher();
+        let time_limits = TimeLimits {
+            execution_time: Some(Duration::from_millis(10)),
+            cpu_time: None,
+        };
+        // The process never finishes on its own
+        let (join, process) =
+            spawn_with_limits(DeadLetters::default(), time_limits, |_, _| async move {
+                futures::future::pending::<()>().await;
+                ExecutionResult {
+                    state: (),
+                    result: ResultValue::Ok,
+                }
+            });
+        process.send(Signal::Monitor(1, Arc::new(watcher.clone())));
+
+        assert!(join.await.is_err());
+        match watcher_join.await.unwrap() {
+            Message::ProcessDown { reason, .. } => {
+                assert_eq!(reason, ExitReason::ExecutionTimeLimit)
+            }
+            _ => panic!("Unexpected message"),
+        }
+    }
+
+    #[async_std::test]
+    async fn cpu_time_limit_kills_process() {
+        let (watcher_join, watcher) = watcher();
+        let time_limits = TimeLimits {
+            execution_time: None,
+            cpu_time: Some(Duration::from_millis(10)),
+        };
+        let (join, process) =
+            spawn_with_limits(DeadLetters::default(), time_limits, |_, mailbox| {
+                async move {
+                    // Wait until the monitor is established
+                    mailbox.pop(None).await;
+                    // Many short slices stay under the limit
+                    for _ in 0..5 {
+                        std::thread::sleep(Duration::from_millis(5));
+                        async_std::task::yield_now().await;
+                    }
+                    // A single long one goes over it
+                    std::thread::sleep(Duration::from_millis(20));
+                    async_std::task::yield_now().await;
+                    ExecutionResult {
+                        state: (),
+                        result: ResultValue::Ok,
+                    }
+                }
+            });
+        process.send(Signal::Monitor(1, Arc::new(watcher.clone())));
+        process.send(Signal::Message(Message::Data(DataMessage::new(None, 0))));
+
+        assert!(join.await.is_err());
+        match watcher_join.await.unwrap() {
+            Message::ProcessDown { reason, .. } => {
+                assert_eq!(reason, ExitReason::CpuTimeLimit)
+            }
+            _ => panic!("Unexpected message"),
+        }
+    }
+
+    #[async_std::test]
+    async fn short_slices_are_not_limited_in_total() {
+        let time_limits = TimeLimits {
+            execution_time: None,
+            cpu_time: Some(Duration::from_millis(10)),
+        };
+        // The handle is kept, the process expects its signal mailbox to stay open
+        let (join, _process) =
+            spawn_with_limits(DeadLetters::default(), time_limits, |_, _| async move {
+                for _ in 0..10 {
+                    std::thread::sleep(Duration::from_millis(5));
+                    async_std::task::yield_now().await;
+                }
+                ExecutionResult {
+                    state: (),
+                    result: ResultValue::Ok,
+                }
+            });
+        assert!(join.await.is_ok());
+    }
+
+    #[async_std::test]
+    async fn huge_execution_time_limit_never_expires() {
+        let time_limits = TimeLimits {
+            execution_time: Some(Duration::MAX),
+            cpu_time: None,
+        };
+        let (join, _process) =
+            spawn_with_limits(DeadLetters::default(), time_limits, |_, _| async move {
+                ExecutionResult {
+                    state: (),
+                    result: ResultValue::Ok,
+                }
+            });
+        assert!(join.await.is_ok());
+    }
+}
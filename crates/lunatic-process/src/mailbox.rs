@@ -1,10 +1,88 @@
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll, Waker};
+use std::time::Instant;
 
-use crate::message::Message;
+use serde::{Deserialize, Serialize};
+
+use crate::message::{DataMessage, Message, MessageKind, MessageMemory};
+
+// Only the most recently abandoned tags and monitors are remembered, so that replies and
+// notifications that never arrive don't accumulate.
+const MAX_ABANDONED: usize = 1024;
+
+/// Defines what happens if a data message arrives at a full mailbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OverflowPolicy {
+    /// Drop the incoming message.
+    #[default]
+    DropNewest,
+    /// Drop the oldest message in the mailbox to make space for the incoming one.
+    DropOldest,
+    /// Drop the incoming message and kill the receiving process.
+    KillReceiver,
+    /// Signal senders that they should back off once the mailbox is three quarters full. Senders
+    /// that ignore the signal and fill the mailbox up get their messages dropped.
+    BackPressure,
+}
+
+/// Decides which messages are matched when looking through a mailbox.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Selector {
+    /// Any message.
+    #[default]
+    Any,
+    /// Messages with one of the tags.
+    Tags(Vec<i64>),
+    /// Messages with a tag inside of the inclusive range.
+    TagRange(i64, i64),
+    /// All messages, except the ones with one of the tags. Messages without a tag are matched.
+    ExceptTags(Vec<i64>),
+    /// Messages of a specific kind.
+    Kind(MessageKind),
+    /// The `ProcessDown` message of the monitor with the reference.
+    Monitor(u64),
+    /// The reply with the tag, or the `ProcessDown` message of the monitor watching the process
+    /// that should reply.
+    Reply { tag: i64, monitor_ref: u64 },
+}
+
+impl Selector {
+    /// Returns true if the message is matched by the selector.
+    pub fn matches(&self, message: &Message) -> bool {
+        match self {
+            Selector::Any => true,
+            Selector::Tags(tags) => matches!(message.tag(), Some(tag) if tags.contains(&tag)),
+            Selector::TagRange(min, max) => {
+                matches!(message.tag(), Some(tag) if (*min..=*max).contains(&tag))
+            }
+            Selector::ExceptTags(tags) => {
+                !matches!(message.tag(), Some(tag) if tags.contains(&tag))
+            }
+            Selector::Kind(kind) => message.kind() == *kind,
+            Selector::Monitor(monitor_ref) => matches!(
+                message,
+                Message::ProcessDown { monitor_ref: r, .. } if r == monitor_ref
+            ),
+            Selector::Reply { tag, monitor_ref } => match message {
+                Message::Data(DataMessage { tag: Some(t), .. }) => t == tag,
+                Message::ProcessDown { monitor_ref: r, .. } => r == monitor_ref,
+                _ => false,
+            },
+        }
+    }
+}
+
+impl From<Option<&[i64]>> for Selector {
+    fn from(tags: Option<&[i64]>) -> Self {
+        match tags {
+            Some(tags) => Selector::Tags(tags.into()),
+            None => Selector::Any,
+        }
+    }
+}
 
 /// The `MessageMailbox` is a data structure holding all messages of a process.
 ///
@@ -12,24 +90,267 @@ use crate::message::Message;
 /// this structure. The order of messages is preserved. This struct also implements the [`Future`]
 /// trait and `pop()` operations can be awaited on if the queue is empty.
 ///
+/// ## Priorities
+///
+/// Messages with a higher priority are kept in front of the ones with a lower priority and are
+/// received first. Messages with the same priority are received in the order they arrived.
+///
 /// ## Safety
 ///
 /// This should be cancellation safe and can be used inside `tokio::select!` statements:
 /// https://docs.rs/tokio/1.10.0/tokio/macro.select.html#cancellation-safety
+///
+/// ## Capacity
+///
+/// A mailbox can be bounded. Once the capacity is reached, the [`OverflowPolicy`] decides what
+/// happens with incoming data messages. Other messages (e.g. `LinkDied`) are always accepted, as
+/// they notify the process about important events.
+///
+/// ## Expiry
+///
+/// Data messages can carry an expiry deadline. Expired messages are discarded once they would be
+/// received and the process waiting on them is notified.
+///
+/// ## Memory
+///
+/// The buffers of delivered messages are counted against the [`MessageMemory`] of the mailbox.
+/// Together with the linear memory of the process they must stay under the memory limit, messages
+/// that would exceed it are rejected.
 #[derive(Clone, Default)]
 pub struct MessageMailbox {
     inner: Arc<Mutex<InnerMessageMailbox>>,
+    memory: MessageMemory,
 }
 
 #[derive(Default)]
 struct InnerMessageMailbox {
     waker: Option<Waker>,
-    tags: Option<Vec<i64>>,
+    selector: Selector,
     found: Option<Message>,
     messages: VecDeque<Message>,
+    capacity: Option<usize>,
+    overflow_policy: OverflowPolicy,
+    abandoned: HashSet<Abandoned>,
+    // Order in which tags and monitors were abandoned, the oldest ones are forgotten first
+    abandoned_order: VecDeque<Abandoned>,
+    dropped_replies: u64,
+    // Messages received in a batch, waiting to be taken out one by one
+    batch: VecDeque<Message>,
+    memory_limit: Option<usize>,
+    // Size of the linear memory of the process, it shares the memory limit with message buffers
+    linear_memory: usize,
+}
+
+// Messages nobody is waiting on anymore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Abandoned {
+    // A data message with the tag.
+    Tag(i64),
+    // The `ProcessDown` message of the monitor.
+    Monitor(u64),
+}
+
+impl Abandoned {
+    fn matches(&self, message: &Message) -> bool {
+        match (self, message) {
+            (Abandoned::Tag(tag), Message::Data(DataMessage { tag: Some(t), .. })) => t == tag,
+            (Abandoned::Monitor(monitor_ref), Message::ProcessDown { monitor_ref: r, .. }) => {
+                r == monitor_ref
+            }
+            _ => false,
+        }
+    }
+}
+
+impl InnerMessageMailbox {
+    /// Removes the abandoned message if it already arrived, otherwise remembers to drop it once it
+    /// arrives. Returns true if the message was removed.
+    fn abandon(&mut self, abandoned: Abandoned) -> bool {
+        // The message could have arrived right before the waiting was canceled.
+        if self
+            .found
+            .as_ref()
+            .is_some_and(|found| abandoned.matches(found))
+        {
+            self.found = None;
+            return true;
+        }
+        if let Some(index) = self.messages.iter().position(|m| abandoned.matches(m)) {
+            self.messages.remove(index);
+            return true;
+        }
+        if self.abandoned.insert(abandoned) {
+            self.abandoned_order.push_back(abandoned);
+            if self.abandoned_order.len() > MAX_ABANDONED {
+                let oldest = self.abandoned_order.pop_front().expect("not empty");
+                self.abandoned.remove(&oldest);
+            }
+        }
+        false
+    }
+
+    /// Returns true if the message was abandoned and forgets about it.
+    fn take_abandoned(&mut self, message: &Message) -> bool {
+        let abandoned = match message {
+            Message::Data(DataMessage { tag: Some(tag), .. }) => Abandoned::Tag(*tag),
+            Message::ProcessDown { monitor_ref, .. } => Abandoned::Monitor(*monitor_ref),
+            _ => return false,
+        };
+        if self.abandoned.remove(&abandoned) {
+            self.abandoned_order.retain(|a| *a != abandoned);
+            true
+        } else {
+            false
+        }
+    }
+
+    // Messages in the batch still take up space in the mailbox until they are taken out.
+    fn len(&self) -> usize {
+        self.messages.len() + self.found.is_some() as usize + self.batch.len()
+    }
+
+    fn is_full(&self) -> bool {
+        match self.capacity {
+            Some(capacity) => self.len() >= capacity,
+            None => false,
+        }
+    }
+
+    /// Puts the message behind all messages with the same or a higher priority.
+    fn enqueue(&mut self, message: Message) {
+        let priority = message.priority();
+        // Most messages have the same priority, this avoids a search through the queue for them.
+        match self.messages.back() {
+            Some(last) if last.priority() < priority => {
+                let index = self
+                    .messages
+                    .iter()
+                    .rposition(|queued| queued.priority() >= priority)
+                    .map_or(0, |index| index + 1);
+                self.messages.insert(index, message);
+            }
+            _ => self.messages.push_back(message),
+        }
+    }
+
+    /// Discards all expired messages.
+    fn purge_expired(&mut self) {
+        let now = Instant::now();
+        let mut index = 0;
+        while index < self.messages.len() {
+            if self.messages[index].is_expired(now) {
+                let expired = self.messages.remove(index).expect("must exist");
+                expired.discard_expired();
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    /// Returns the index of the first message matched by the `selector`, discarding all expired
+    /// messages in front of it.
+    fn first_matching(&mut self, selector: &Selector) -> Option<usize> {
+        let now = Instant::now();
+        let mut start = 0;
+        loop {
+            let index = start
+                + self
+                    .messages
+                    .iter()
+                    .skip(start)
+                    .position(|message| selector.matches(message))?;
+            if self.messages[index].is_expired(now) {
+                let expired = self.messages.remove(index).expect("must exist");
+                expired.discard_expired();
+                start = index;
+            } else {
+                return Some(index);
+            }
+        }
+    }
 }
 
 impl MessageMailbox {
+    /// Create a new mailbox holding up to `capacity` messages and `memory_limit` bytes of memory.
+    /// If `capacity` or `memory_limit` is `None` the mailbox is unbounded.
+    pub fn new(
+        capacity: Option<usize>,
+        overflow_policy: OverflowPolicy,
+        memory_limit: Option<usize>,
+    ) -> Self {
+        let inner = InnerMessageMailbox {
+            capacity,
+            overflow_policy,
+            memory_limit,
+            ..Default::default()
+        };
+        Self {
+            inner: Arc::new(Mutex::new(inner)),
+            memory: MessageMemory::new(),
+        }
+    }
+
+    /// Marks the `tag` as abandoned. The next data message arriving with this tag is dropped.
+    ///
+    /// It's used if the process stops waiting on a reply, so that a late reply doesn't stay in
+    /// the mailbox forever. If the reply already arrived, it's dropped right away.
+    pub fn abandon(&self, tag: i64) {
+        let mut mailbox = self.inner.lock().expect("never poisoned");
+        if mailbox.abandon(Abandoned::Tag(tag)) {
+            mailbox.dropped_replies += 1;
+        }
+    }
+
+    /// Marks the monitor as abandoned. Its `ProcessDown` message is dropped, also if it already
+    /// arrived.
+    ///
+    /// It's used for monitors that only watch a process while waiting on its reply.
+    pub fn abandon_monitor(&self, monitor_ref: u64) {
+        let mut mailbox = self.inner.lock().expect("never poisoned");
+        mailbox.abandon(Abandoned::Monitor(monitor_ref));
+    }
+
+    /// Returns the memory used by the buffers of messages held by the process.
+    pub fn memory(&self) -> &MessageMemory {
+        &self.memory
+    }
+
+    /// Sets the size of the linear memory of the process, which counts against the same memory
+    /// limit as the buffers of received messages.
+    pub fn set_linear_memory(&self, size: usize) {
+        let mut mailbox = self.inner.lock().expect("never poisoned");
+        mailbox.linear_memory = size;
+    }
+
+    /// Returns the number of replies that were dropped, because nobody was waiting on them
+    /// anymore.
+    pub fn dropped_replies(&self) -> u64 {
+        let mailbox = self.inner.lock().expect("never poisoned");
+        mailbox.dropped_replies
+    }
+
+    /// Returns the policy that is applied if the mailbox is full.
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        let mailbox = self.inner.lock().expect("never poisoned");
+        mailbox.overflow_policy
+    }
+
+    /// Returns true if senders should back off, because the mailbox uses the
+    /// [`OverflowPolicy::BackPressure`] policy and is three quarters full.
+    ///
+    /// `pending` messages that were sent, but didn't arrive in the mailbox yet, are also counted.
+    pub fn back_pressure(&self, pending: usize) -> bool {
+        let mailbox = self.inner.lock().expect("never poisoned");
+        match (mailbox.overflow_policy, mailbox.capacity) {
+            (OverflowPolicy::BackPressure, Some(capacity)) => {
+                // Leave some room for messages that are already on their way when senders back off
+                let threshold = capacity - capacity / 4;
+                mailbox.len() + pending >= threshold
+            }
+            _ => false,
+        }
+    }
+
     /// Return message in FIFO order from mailbox.
     ///
     /// If function is called with a `tags` value different from None, it will only return the first
@@ -37,42 +358,84 @@ impl MessageMailbox {
     ///
     /// If no message exist, blocks until a message is received.
     pub async fn pop(&self, tags: Option<&[i64]>) -> Message {
+        self.pop_select(tags.into()).await
+    }
+
+    /// Return the first message matched by the `selector` from mailbox.
+    ///
+    /// If no matching message exist, blocks until one is received.
+    pub async fn pop_select(&self, selector: Selector) -> Message {
         // Mailbox lock must be released before .await
         {
-            let mut mailbox = self.inner.lock().expect("only accessed by one process");
+            let mut mailbox = self.inner.lock().expect("never poisoned");
 
             // If a found message exists here, it means that the previous `.await` was canceled
             // after a `wake()` call. To not lose this message it should be put into the queue.
             if let Some(found) = mailbox.found.take() {
-                mailbox.messages.push_back(found);
+                mailbox.enqueue(found);
             }
 
-            // When looking for specific tags, loop through all messages to check for it
-            if let Some(tags) = tags {
-                let index = mailbox.messages.iter().position(|x| {
-                    // Only consider messages that also have a tag.
-                    if let Some(tag) = x.tag() {
-                        tags.contains(&tag)
-                    } else {
-                        false
-                    }
-                });
-                // If message matching tags is found, remove it.
-                if let Some(index) = index {
-                    return mailbox.messages.remove(index).expect("must exist");
-                }
-            } else {
-                // If not looking for a specific tags try to pop the first message available.
-                if let Some(message) = mailbox.messages.pop_front() {
-                    return message;
-                }
+            // Loop through all messages to check for a matching one
+            let index = mailbox.first_matching(&selector);
+            // If a matching message is found, remove it.
+            if let Some(index) = index {
+                return mailbox.messages.remove(index).expect("must exist");
             }
-            // Mark the tags to wait on.
-            mailbox.tags = tags.map(|tags| tags.into());
+            // Mark the selector to wait on.
+            mailbox.selector = selector;
         }
         self.await
     }
 
+    /// Moves up to `max` messages matched by the `selector` into the batch and returns how many
+    /// were moved. They can be taken out of the batch with [`next_in_batch`](Self::next_in_batch).
+    ///
+    /// Blocks until at least one matching message is received, but doesn't wait on more messages
+    /// than are already in the mailbox. Messages in the batch still count against the capacity.
+    pub async fn pop_into_batch(&self, max: usize, selector: Selector) -> usize {
+        if max == 0 {
+            return 0;
+        }
+        let first = self.pop_select(selector.clone()).await;
+        let mut mailbox = self.inner.lock().expect("never poisoned");
+        mailbox.batch.push_back(first);
+        let mut received = 1;
+        while received < max {
+            match mailbox.first_matching(&selector) {
+                Some(index) => {
+                    let message = mailbox.messages.remove(index).expect("must exist");
+                    mailbox.batch.push_back(message);
+                    received += 1;
+                }
+                None => break,
+            }
+        }
+        received
+    }
+
+    /// Takes the next message out of the batch.
+    pub fn next_in_batch(&self) -> Option<Message> {
+        let mut mailbox = self.inner.lock().expect("never poisoned");
+        mailbox.batch.pop_front()
+    }
+
+    /// Calls `f` with the first message matched by the `selector`, without removing it from the
+    /// mailbox.
+    ///
+    /// Returns None if no message matches. This function never blocks.
+    pub fn peek<F, R>(&self, selector: &Selector, f: F) -> Option<R>
+    where
+        F: FnOnce(&Message) -> R,
+    {
+        let mut mailbox = self.inner.lock().expect("never poisoned");
+        // Put back a message from a canceled `.await`, so that the order is the same as for `pop`.
+        if let Some(found) = mailbox.found.take() {
+            mailbox.enqueue(found);
+        }
+        let index = mailbox.first_matching(selector)?;
+        Some(f(&mailbox.messages[index]))
+    }
+
     /// Similar to `pop`, but will assume right away that no message with this tags exists.
     ///
     /// Sometimes we know that the message we are waiting on can't have a particular tags already in
@@ -93,18 +456,24 @@ impl MessageMailbox {
     /// doesn't contain any `.await` calls in-between. This implementation detail can be hidden
     /// inside of atomic host function calls so that end users don't need to worry about it.
     pub async fn pop_skip_search(&self, tags: Option<&[i64]>) -> Message {
+        self.pop_skip_search_select(tags.into()).await
+    }
+
+    /// Similar to `pop_select`, but will assume right away that no message matched by the
+    /// `selector` exists. See [`pop_skip_search`](Self::pop_skip_search) for when this is safe.
+    pub async fn pop_skip_search_select(&self, selector: Selector) -> Message {
         // Mailbox lock must be released before .await
         {
-            let mut mailbox = self.inner.lock().expect("only accessed by one process");
+            let mut mailbox = self.inner.lock().expect("never poisoned");
 
             // If a found message exists here, it means that the previous `.await` was canceled
             // after a `wake()` call. To not lose this message it should be put into the queue.
             if let Some(found) = mailbox.found.take() {
-                mailbox.messages.push_back(found);
+                mailbox.enqueue(found);
             }
 
-            // Mark the tags to wait on.
-            mailbox.tags = tags.map(|tags| tags.into());
+            // Mark the selector to wait on.
+            mailbox.selector = selector;
         }
         self.await
     }
@@ -113,30 +482,116 @@ impl MessageMailbox {
     ///
     /// If the message is being .awaited on, this call will immediately notify the waker that it's
     /// ready, otherwise it will push it at the end of the queue.
-    pub fn push(&self, message: Message) {
-        let mut mailbox = self.inner.lock().expect("only accessed by one process");
+    ///
+    /// Returns false if a message was dropped because the mailbox is full or the message would
+    /// exceed the memory limit.
+    pub fn push(&self, message: Message) -> bool {
+        self.try_push(message).unwrap_or(false)
+    }
+
+    /// Same as [`push`](Self::push), but hands back a message that would exceed the memory limit
+    /// of the process, so that it can be turned into a dead letter.
+    pub fn try_push(&self, mut message: Message) -> Result<bool, Box<Message>> {
+        let mut mailbox = self.inner.lock().expect("never poisoned");
+        // Drop late replies and notifications nobody is waiting on anymore.
+        if mailbox.take_abandoned(&message) {
+            if matches!(message, Message::Data(_)) {
+                mailbox.dropped_replies += 1;
+            }
+            return Ok(true);
+        }
+        if let Some(memory_limit) = mailbox.memory_limit {
+            let memory = mailbox.linear_memory + self.memory.used() + message.memory_usage();
+            if memory > memory_limit {
+                return Err(Box::new(message));
+            }
+        }
+        message.charge_to(&self.memory);
         // If waiting on a new message notify executor that it arrived.
         if let Some(waker) = mailbox.waker.take() {
-            // If waiting on specific tags only notify if tags are matched, otherwise forward every message.
-            // Note that because of the short-circuit rule in Rust it's safe to use `unwrap()` here.
-            if mailbox.tags.is_none()
-                || (message.tag().is_some()
-                    && mailbox
-                        .tags
-                        .as_ref()
-                        .unwrap()
-                        .contains(&message.tag().unwrap()))
-            {
+            // Only notify if the message is matched by the selector we are waiting on.
+            if mailbox.selector.matches(&message) {
+                // A message that is already expired is never received.
+                if message.is_expired(Instant::now()) {
+                    mailbox.waker = Some(waker);
+                    message.discard_expired();
+                    return Ok(true);
+                }
                 mailbox.found = Some(message);
                 waker.wake();
-                return;
+                return Ok(true);
             } else {
                 // Put the waker back if this is not the message we are looking for.
                 mailbox.waker = Some(waker);
             }
         }
+        // Expired messages would never be received, so they don't take up space in a full mailbox.
+        let is_data = matches!(message, Message::Data(_));
+        if is_data && mailbox.is_full() {
+            mailbox.purge_expired();
+        }
+        // Apply the overflow policy if the mailbox is full
+        let mut accepted = true;
+        if is_data && mailbox.is_full() {
+            match mailbox.overflow_policy {
+                // Back-pressure is only a request to senders, the capacity is still enforced.
+                OverflowPolicy::DropNewest
+                | OverflowPolicy::KillReceiver
+                | OverflowPolicy::BackPressure => return Ok(false),
+                OverflowPolicy::DropOldest => {
+                    // Only messages with the lowest priority are considered, so that high
+                    // priority messages are not pushed out by a flood of regular ones.
+                    let is_data = |message: &Message| matches!(message, Message::Data(_));
+                    let lowest = mailbox
+                        .messages
+                        .iter()
+                        .filter(|message| is_data(message))
+                        .map(Message::priority)
+                        .min();
+                    let oldest = mailbox
+                        .messages
+                        .iter()
+                        .position(|message| is_data(message) && Some(message.priority()) == lowest);
+                    match oldest {
+                        Some(oldest) => {
+                            mailbox.messages.remove(oldest);
+                            accepted = false;
+                        }
+                        // All data is already in the batch, so the incoming message is dropped.
+                        None => return Ok(false),
+                    }
+                }
+            }
+        }
         // Otherwise put message into queue
-        mailbox.messages.push_back(message);
+        mailbox.enqueue(message);
+        Ok(accepted)
+    }
+
+    /// Returns the number of messages waiting in the mailbox.
+    pub fn len(&self) -> usize {
+        let mailbox = self.inner.lock().expect("never poisoned");
+        mailbox.len()
+    }
+
+    /// Returns true if there are no messages waiting in the mailbox.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops all messages waiting in the mailbox.
+    ///
+    /// Handles to a process share its mailbox, so it's cleared once the process finishes. Otherwise
+    /// the messages and their resources would stay alive as long as any handle exists.
+    pub fn clear(&self) {
+        let mut mailbox = self.inner.lock().expect("never poisoned");
+        let messages = std::mem::take(&mut mailbox.messages);
+        let batch = std::mem::take(&mut mailbox.batch);
+        let found = mailbox.found.take();
+        mailbox.waker = None;
+        // Resources of the messages are released outside of the lock
+        drop(mailbox);
+        drop((messages, batch, found));
     }
 }
 
@@ -144,7 +599,7 @@ impl Future for &MessageMailbox {
     type Output = Message;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut mailbox = self.inner.lock().expect("only accessed by one process");
+        let mut mailbox = self.inner.lock().expect("never poisoned");
         if let Some(message) = mailbox.found.take() {
             Poll::Ready(message)
         } else {
@@ -158,29 +613,346 @@ impl Future for &MessageMailbox {
 mod tests {
     use std::{
         future::Future,
+        io::Write,
         sync::{Arc, Mutex},
         task::{Context, Poll, Wake},
+        time::{Duration, Instant},
     };
 
-    use super::{Message, MessageMailbox};
+    use uuid::Uuid;
+
+    use super::{Message, MessageMailbox, OverflowPolicy, Selector, MAX_ABANDONED};
+    use crate::{
+        message::{DataMessage, Expiry, MessageKind, MessageMemory},
+        ExitReason,
+    };
 
     #[async_std::test]
     async fn no_tags_signal_message() {
         let mailbox = MessageMailbox::default();
-        let message = Message::LinkDied(None);
+        let message = Message::LinkDied(None, ExitReason::Normal);
         mailbox.push(message);
         let result = mailbox.pop(None).await;
         match result {
-            Message::LinkDied(None) => (),
+            Message::LinkDied(None, _) => (),
             _ => panic!("Wrong message received"),
         }
     }
 
+    #[async_std::test]
+    async fn full_mailbox_applies_overflow_policy() {
+        let data = |tag| Message::Data(DataMessage::new(Some(tag), 0));
+
+        let mailbox = MessageMailbox::new(Some(2), OverflowPolicy::DropNewest, None);
+        assert!(mailbox.push(data(1)));
+        assert!(mailbox.push(data(2)));
+        assert!(!mailbox.push(data(3)));
+        // Signal messages are accepted even if the mailbox is full
+        assert!(mailbox.push(Message::LinkDied(None, ExitReason::Normal)));
+        assert_eq!(mailbox.len(), 3);
+        assert_eq!(mailbox.pop(None).await.tag(), Some(1));
+
+        let mailbox = MessageMailbox::new(Some(2), OverflowPolicy::DropOldest, None);
+        mailbox.push(data(1));
+        mailbox.push(data(2));
+        assert!(!mailbox.push(data(3)));
+        assert_eq!(mailbox.pop(None).await.tag(), Some(2));
+        assert_eq!(mailbox.pop(None).await.tag(), Some(3));
+
+        let mailbox = MessageMailbox::new(Some(4), OverflowPolicy::BackPressure, None);
+        assert!(!mailbox.back_pressure(0));
+        assert!(mailbox.back_pressure(3));
+        for tag in 1..=3 {
+            assert!(mailbox.push(data(tag)));
+        }
+        assert!(mailbox.back_pressure(0));
+        // Senders ignoring the back-pressure can still fill up the mailbox, but not overflow it
+        assert!(mailbox.push(data(4)));
+        assert!(!mailbox.push(data(5)));
+        assert_eq!(mailbox.len(), 4);
+    }
+
+    #[async_std::test]
+    async fn higher_priority_messages_are_received_first() {
+        let mailbox = MessageMailbox::default();
+        for (tag, priority) in [(1, 0), (2, 5), (3, 0), (4, 9), (5, 5)] {
+            let mut message = DataMessage::new(Some(tag), 0);
+            message.priority = priority;
+            mailbox.push(Message::Data(message));
+        }
+        mailbox.push(Message::LinkDied(Some(6), ExitReason::Normal));
+
+        let mut received = Vec::new();
+        while !mailbox.is_empty() {
+            received.push(mailbox.pop(None).await.tag().unwrap());
+        }
+        assert_eq!(received, vec![4, 2, 5, 1, 3, 6]);
+
+        // Shutdown requests jump ahead of all data
+        let mut message = DataMessage::new(None, 0);
+        message.priority = 9;
+        mailbox.push(Message::Data(message));
+        mailbox.push(Message::Shutdown);
+        assert!(matches!(mailbox.pop(None).await, Message::Shutdown));
+    }
+
+    #[async_std::test]
+    async fn pop_into_batch_drains_matching_messages() {
+        let mailbox = MessageMailbox::default();
+        for tag in 1..=5 {
+            mailbox.push(Message::LinkDied(Some(tag), ExitReason::Normal));
+        }
+
+        assert_eq!(
+            mailbox
+                .pop_into_batch(2, Selector::ExceptTags(vec![1]))
+                .await,
+            2
+        );
+        // Doesn't wait on more messages than there are in the mailbox
+        assert_eq!(mailbox.pop_into_batch(10, Selector::Any).await, 3);
+        let mut tags = Vec::new();
+        while let Some(message) = mailbox.next_in_batch() {
+            tags.push(message.tag().unwrap());
+        }
+        assert_eq!(tags, vec![2, 3, 1, 4, 5]);
+        assert!(mailbox.is_empty());
+    }
+
+    #[async_std::test]
+    async fn batched_messages_count_against_capacity() {
+        let data = |tag| Message::Data(DataMessage::new(Some(tag), 0));
+        let mailbox = MessageMailbox::new(Some(2), OverflowPolicy::DropOldest, None);
+        mailbox.push(data(1));
+        mailbox.push(data(2));
+        assert_eq!(mailbox.pop_into_batch(2, Selector::Any).await, 2);
+        assert_eq!(mailbox.len(), 2);
+        assert!(!mailbox.push(data(3)));
+        assert_eq!(mailbox.next_in_batch().unwrap().tag(), Some(1));
+        assert!(mailbox.push(data(3)));
+    }
+
+    #[async_std::test]
+    async fn expired_messages_are_discarded() {
+        let mailbox = MessageMailbox::default();
+        let mut expired = DataMessage::new(Some(1), 0);
+        expired.expiry = Some(Expiry {
+            deadline: Instant::now(),
+            notify: None,
+        });
+        let mut alive = DataMessage::new(Some(2), 0);
+        alive.expiry = Some(Expiry {
+            deadline: Instant::now() + Duration::from_secs(60),
+            notify: None,
+        });
+        mailbox.push(Message::Data(expired));
+        mailbox.push(Message::Data(alive));
+        mailbox.push(Message::Data(DataMessage::new(Some(3), 0)));
+
+        assert_eq!(
+            mailbox.peek(&Selector::Any, |message| message.tag()),
+            Some(Some(2))
+        );
+        assert_eq!(mailbox.pop(None).await.tag(), Some(2));
+        assert_eq!(mailbox.pop(None).await.tag(), Some(3));
+        assert!(mailbox.is_empty());
+    }
+
+    #[async_std::test]
+    async fn expired_messages_make_space_in_full_mailbox() {
+        let mailbox = MessageMailbox::new(Some(2), OverflowPolicy::DropNewest, None);
+        let mut expiring = DataMessage::new(Some(1), 0);
+        expiring.expiry = Some(Expiry {
+            deadline: Instant::now() + Duration::from_millis(10),
+            notify: None,
+        });
+        mailbox.push(Message::Data(expiring));
+        mailbox.push(Message::Data(DataMessage::new(Some(2), 0)));
+        async_std::task::sleep(Duration::from_millis(20)).await;
+
+        assert!(mailbox.push(Message::Data(DataMessage::new(Some(3), 0))));
+        assert_eq!(mailbox.len(), 2);
+        assert_eq!(mailbox.pop(None).await.tag(), Some(2));
+        assert_eq!(mailbox.pop(None).await.tag(), Some(3));
+    }
+
+    #[async_std::test]
+    async fn abandoned_replies_are_dropped() {
+        let mailbox = MessageMailbox::default();
+        mailbox.abandon(-1);
+        mailbox.push(Message::Data(DataMessage::new(Some(-1), 0)));
+        assert!(mailbox.is_empty());
+        // Only the first reply is dropped
+        mailbox.push(Message::Data(DataMessage::new(Some(-1), 0)));
+        assert_eq!(mailbox.pop(None).await.tag(), Some(-1));
+        // A reply that is already in the mailbox is dropped right away
+        mailbox.push(Message::Data(DataMessage::new(Some(-2), 0)));
+        mailbox.abandon(-2);
+        assert!(mailbox.is_empty());
+        assert_eq!(mailbox.dropped_replies(), 2);
+
+        // Tags of replies that never arrive are eventually forgotten
+        for tag in 0..MAX_ABANDONED as i64 + 1 {
+            mailbox.abandon(tag);
+        }
+        mailbox.push(Message::Data(DataMessage::new(Some(0), 0)));
+        mailbox.push(Message::Data(DataMessage::new(Some(1), 0)));
+        assert_eq!(mailbox.len(), 1);
+    }
+
+    #[async_std::test]
+    async fn abandoned_monitors_are_dropped() {
+        let mailbox = MessageMailbox::default();
+        let process_down = |monitor_ref| Message::ProcessDown {
+            monitor_ref,
+            process_id: Uuid::new_v4(),
+            reason: ExitReason::Normal,
+        };
+        mailbox.abandon_monitor(1);
+        mailbox.push(process_down(1));
+        assert!(mailbox.is_empty());
+        mailbox.push(process_down(2));
+        mailbox.abandon_monitor(2);
+        assert!(mailbox.is_empty());
+        // Notifications are not replies
+        assert_eq!(mailbox.dropped_replies(), 0);
+    }
+
+    #[async_std::test]
+    async fn reply_selector_matches_reply_or_process_down() {
+        let mailbox = MessageMailbox::default();
+        let selector = Selector::Reply {
+            tag: -1,
+            monitor_ref: 7,
+        };
+        mailbox.push(Message::Data(DataMessage::new(Some(1), 0)));
+        mailbox.push(Message::ProcessDown {
+            monitor_ref: 8,
+            process_id: Uuid::new_v4(),
+            reason: ExitReason::Normal,
+        });
+        mailbox.push(Message::Data(DataMessage::new(Some(-1), 0)));
+        mailbox.push(Message::ProcessDown {
+            monitor_ref: 7,
+            process_id: Uuid::new_v4(),
+            reason: ExitReason::Normal,
+        });
+        assert_eq!(mailbox.pop_select(selector.clone()).await.tag(), Some(-1));
+        assert!(matches!(
+            mailbox.pop_select(selector).await,
+            Message::ProcessDown { monitor_ref: 7, .. }
+        ));
+        assert_eq!(mailbox.len(), 2);
+    }
+
+    #[async_std::test]
+    async fn message_memory_moves_to_the_receiver() {
+        let sender = MessageMemory::new();
+        let mailbox = MessageMailbox::default();
+        let mut message = DataMessage::new(None, 0);
+        message.charge_to(&sender);
+        message.write_all(&[0; 100]).unwrap();
+        let used = sender.used();
+        assert!(used >= 100);
+
+        mailbox.push(Message::Data(message));
+        assert_eq!(sender.used(), 0);
+        assert_eq!(mailbox.memory().used(), used);
+
+        drop(mailbox.pop(None).await);
+        assert_eq!(mailbox.memory().used(), 0);
+    }
+
+    #[async_std::test]
+    async fn messages_over_the_memory_limit_are_rejected() {
+        let mailbox = MessageMailbox::new(None, OverflowPolicy::default(), Some(200));
+        let data = |size: usize| {
+            let mut message = DataMessage::new(None, 0);
+            message.write_all(&vec![0; size]).unwrap();
+            Message::Data(message)
+        };
+        assert!(mailbox.try_push(data(100)).is_ok());
+        assert_eq!(mailbox.memory().used(), 100);
+        assert!(mailbox.try_push(data(150)).is_err());
+        assert!(!mailbox.push(data(150)));
+        assert_eq!(mailbox.memory().used(), 100);
+
+        // The linear memory counts against the same limit
+        mailbox.set_linear_memory(100);
+        assert!(mailbox.try_push(data(1)).is_err());
+        mailbox.set_linear_memory(0);
+        assert!(mailbox.try_push(data(100)).is_ok());
+        assert_eq!(mailbox.len(), 2);
+    }
+
+    #[test]
+    fn writes_are_counted_by_the_written_size() {
+        let mut message = DataMessage::new(None, 0);
+        for _ in 0..3 {
+            message.write_all(&[0; 100]).unwrap();
+        }
+        assert_eq!(message.memory_usage(), 300);
+
+        let mut message = DataMessage::new(None, 200);
+        message.write_all(&[0; 100]).unwrap();
+        assert_eq!(message.memory_usage(), 200);
+    }
+
+    #[async_std::test]
+    async fn peek_and_pop_with_selectors() {
+        let mailbox = MessageMailbox::default();
+        for monitor_ref in [1, 2] {
+            mailbox.push(Message::ProcessDown {
+                monitor_ref,
+                process_id: Uuid::new_v4(),
+                reason: ExitReason::Normal,
+            });
+        }
+        let message = mailbox.pop_select(Selector::Monitor(2)).await;
+        assert!(matches!(
+            message,
+            Message::ProcessDown { monitor_ref: 2, .. }
+        ));
+        let message = mailbox.pop_select(Selector::Monitor(1)).await;
+        assert!(matches!(
+            message,
+            Message::ProcessDown { monitor_ref: 1, .. }
+        ));
+
+        mailbox.push(Message::Data(DataMessage::new(Some(1), 0)));
+        mailbox.push(Message::LinkDied(Some(15), ExitReason::Normal));
+        mailbox.push(Message::Data(DataMessage::new(None, 0)));
+        mailbox.push(Message::Data(DataMessage::new(Some(20), 0)));
+
+        // Peeking doesn't remove messages
+        let selector = Selector::TagRange(10, 20);
+        assert_eq!(
+            mailbox.peek(&selector, |message| message.tag()),
+            Some(Some(15))
+        );
+        assert_eq!(
+            mailbox.peek(&selector, |message| message.tag()),
+            Some(Some(15))
+        );
+        assert_eq!(mailbox.len(), 4);
+        assert_eq!(mailbox.peek(&Selector::Tags(vec![2]), |_| ()), None);
+
+        let message = mailbox.pop_select(Selector::Kind(MessageKind::Data)).await;
+        assert_eq!(message.tag(), Some(1));
+        let message = mailbox.pop_select(Selector::ExceptTags(vec![15])).await;
+        assert_eq!(message.tag(), None);
+        let message = mailbox.pop_select(selector).await;
+        assert_eq!(message.tag(), Some(15));
+        let message = mailbox.pop_select(Selector::Any).await;
+        assert_eq!(message.tag(), Some(20));
+        assert!(mailbox.is_empty());
+    }
+
     #[async_std::test]
     async fn tag_signal_message() {
         let mailbox = MessageMailbox::default();
         let tag = 1337;
-        let message = Message::LinkDied(Some(tag));
+        let message = Message::LinkDied(Some(tag), ExitReason::Normal);
         mailbox.push(message);
         let message = mailbox.pop(None).await;
         assert_eq!(message.tag(), Some(tag));
@@ -194,11 +966,11 @@ mod tests {
         let tag3 = 3;
         let tag4 = 4;
         let tag5 = 5;
-        mailbox.push(Message::LinkDied(Some(tag1)));
-        mailbox.push(Message::LinkDied(Some(tag2)));
-        mailbox.push(Message::LinkDied(Some(tag3)));
-        mailbox.push(Message::LinkDied(Some(tag4)));
-        mailbox.push(Message::LinkDied(Some(tag5)));
+        mailbox.push(Message::LinkDied(Some(tag1), ExitReason::Normal));
+        mailbox.push(Message::LinkDied(Some(tag2), ExitReason::Normal));
+        mailbox.push(Message::LinkDied(Some(tag3), ExitReason::Normal));
+        mailbox.push(Message::LinkDied(Some(tag4), ExitReason::Normal));
+        mailbox.push(Message::LinkDied(Some(tag5), ExitReason::Normal));
         let message = mailbox.pop(Some(&[tag2])).await;
         assert_eq!(message.tag(), Some(tag2));
         let message = mailbox.pop(Some(&[tag1])).await;
@@ -220,11 +992,11 @@ mod tests {
         let tag3 = 3;
         let tag4 = 4;
         let tag5 = 5;
-        mailbox.push(Message::LinkDied(Some(tag1)));
-        mailbox.push(Message::LinkDied(Some(tag2)));
-        mailbox.push(Message::LinkDied(Some(tag3)));
-        mailbox.push(Message::LinkDied(Some(tag4)));
-        mailbox.push(Message::LinkDied(Some(tag5)));
+        mailbox.push(Message::LinkDied(Some(tag1), ExitReason::Normal));
+        mailbox.push(Message::LinkDied(Some(tag2), ExitReason::Normal));
+        mailbox.push(Message::LinkDied(Some(tag3), ExitReason::Normal));
+        mailbox.push(Message::LinkDied(Some(tag4), ExitReason::Normal));
+        mailbox.push(Message::LinkDied(Some(tag5), ExitReason::Normal));
         let message = mailbox.pop(Some(&[tag2, tag1, tag3])).await;
         assert_eq!(message.tag(), Some(tag1));
         let message = mailbox.pop(Some(&[tag2, tag1, tag3])).await;
@@ -265,7 +1037,7 @@ mod tests {
         assert!(result.is_pending());
         assert_eq!(*waker_ref.0.lock().unwrap(), false);
         // Pushing a message to the mailbox will call the waker
-        mailbox.push(Message::LinkDied(tags));
+        mailbox.push(Message::LinkDied(tags, ExitReason::Normal));
         assert_eq!(*waker_ref.0.lock().unwrap(), true);
         // Next poll will return the value
         let result = fut.as_mut().poll(&mut context);
@@ -288,15 +1060,15 @@ mod tests {
         assert!(result.is_pending());
         assert_eq!(*waker_ref.0.lock().unwrap(), false);
         // Pushing a message with the `None` tags should not trigger the waker
-        mailbox.push(Message::LinkDied(None));
+        mailbox.push(Message::LinkDied(None, ExitReason::Normal));
         assert_eq!(*waker_ref.0.lock().unwrap(), false);
         // Next poll will still not have the value with the tags 1337
         let result = fut.as_mut().poll(&mut context);
         assert!(result.is_pending());
         // Pushing another None in the meantime should not remove the waker
-        mailbox.push(Message::LinkDied(None));
+        mailbox.push(Message::LinkDied(None, ExitReason::Normal));
         // Pushing a message with tags 1337 should trigger the waker
-        mailbox.push(Message::LinkDied(Some(1337)));
+        mailbox.push(Message::LinkDied(Some(1337), ExitReason::Normal));
         assert_eq!(*waker_ref.0.lock().unwrap(), true);
         // Next poll will have the message ready
         let result = fut.as_mut().poll(&mut context);
@@ -318,7 +1090,7 @@ mod tests {
         assert!(result.is_pending());
         assert_eq!(*waker_ref.0.lock().unwrap(), false);
         // Pushing a message with the `None` tags should call the waker()
-        mailbox.push(Message::LinkDied(None));
+        mailbox.push(Message::LinkDied(None, ExitReason::Normal));
         assert_eq!(*waker_ref.0.lock().unwrap(), true);
         // Dropping the future will cancel it
         drop(fut);
@@ -333,7 +1105,7 @@ mod tests {
         let result = fut.poll(&mut context);
         match result {
             Poll::Ready(message) => match message {
-                Message::LinkDied(tags) => assert_eq!(tags, None),
+                Message::LinkDied(tags, _) => assert_eq!(tags, None),
                 _ => panic!("Unexpected message"),
             },
             _ => panic!("Unexpected message"),
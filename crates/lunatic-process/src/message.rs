@@ -1,39 +1,249 @@
 /*!
 The [`Message`] is a special variant of a [`Signal`](crate::Signal) that can be sent to
 processes. The most common kind of Message is a [`DataMessage`], but there are also some special
-kinds of messages, like the [`Message::LinkDied`], that is received if a linked process dies.
+kinds of messages, like the [`Message::LinkDied`], that is received if a linked process dies, the
+[`Message::ProcessDown`], that is received if a monitored process finishes, the
+[`Message::Shutdown`], that is received if the process is asked to shut down, or the
+[`Message::DeadLetter`], that is received by the dead-letter sink.
 */
 
 use std::{
+    any::Any,
     fmt::Debug,
     io::{Read, Write},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
 };
 
-use async_std::net::{TcpStream, UdpSocket};
+use async_std::net::{TcpListener, TcpStream, UdpSocket};
+use uuid::Uuid;
 
-use crate::Process;
+use crate::{ExitReason, Process, Signal};
 
 /// Can be sent between processes by being embedded into a  [`Signal::Message`][0]
 ///
-/// A [`Message`] has 2 variants:
+/// A [`Message`] has 5 variants:
 /// * Data - Regular message containing a tag, buffer and resources.
 /// * LinkDied - A `LinkDied` signal that was turned into a message.
+/// * ProcessDown - Notification that a monitored process finished.
+/// * Shutdown - A `Shutdown` signal that was turned into a message.
+/// * DeadLetter - A message that couldn't be delivered, forwarded to the dead-letter sink.
 ///
 /// [0]: crate::Signal
 #[derive(Debug)]
 pub enum Message {
     Data(DataMessage),
-    LinkDied(Option<i64>),
+    LinkDied(Option<i64>, ExitReason),
+    ProcessDown {
+        // Reference returned when the monitor was created
+        monitor_ref: u64,
+        // ID of the process that finished
+        process_id: Uuid,
+        // Reason of the process finishing
+        reason: ExitReason,
+    },
+    Shutdown,
+    DeadLetter {
+        // ID of the process the message was sent to
+        recipient: Uuid,
+        message: Box<Message>,
+    },
 }
 
 impl Message {
     pub fn tag(&self) -> Option<i64> {
         match self {
             Message::Data(message) => message.tag,
-            Message::LinkDied(tag) => *tag,
+            Message::LinkDied(tag, _) => *tag,
+            Message::ProcessDown { .. } | Message::Shutdown | Message::DeadLetter { .. } => None,
         }
     }
+
+    /// Returns the priority of the message. Shutdown requests have the highest priority, so that
+    /// they are received before any queued data. Other messages have a priority of 0.
+    pub fn priority(&self) -> u32 {
+        match self {
+            Message::Data(message) => message.priority,
+            Message::Shutdown => u32::MAX,
+            _ => 0,
+        }
+    }
+
+    /// Returns true if it's a data message whose expiry deadline passed.
+    pub fn is_expired(&self, now: Instant) -> bool {
+        match self {
+            Message::Data(DataMessage {
+                expiry: Some(expiry),
+                ..
+            }) => expiry.deadline <= now,
+            _ => false,
+        }
+    }
+
+    /// Drops an expired message and notifies the process waiting on it, if there is one.
+    pub fn discard_expired(self) {
+        if let Message::Data(DataMessage {
+            expiry:
+                Some(Expiry {
+                    notify: Some((process, tag)),
+                    ..
+                }),
+            ..
+        }) = self
+        {
+            let notification = DataMessage::new(Some(tag), 0);
+            process.send(Signal::Message(Message::Data(notification)));
+        }
+    }
+
+    /// Counts the buffer of the message against `memory`, instead of the process that was holding
+    /// on to it until now.
+    pub fn charge_to(&mut self, memory: &MessageMemory) {
+        match self {
+            Message::Data(data) => data.charge_to(memory),
+            Message::DeadLetter { message, .. } => message.charge_to(memory),
+            _ => (),
+        }
+    }
+
+    /// Returns the number of bytes the message counts against the memory of the process holding
+    /// on to it.
+    pub fn memory_usage(&self) -> usize {
+        match self {
+            Message::Data(data) => data.memory_usage(),
+            Message::DeadLetter { message, .. } => message.memory_usage(),
+            _ => 0,
+        }
+    }
+
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            Message::Data(_) => MessageKind::Data,
+            Message::LinkDied(..) => MessageKind::LinkDied,
+            Message::ProcessDown { .. } => MessageKind::ProcessDown,
+            Message::Shutdown => MessageKind::Shutdown,
+            Message::DeadLetter { .. } => MessageKind::DeadLetter,
+        }
+    }
+}
+
+/// The variant of a [`Message`], without any of its data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Data,
+    LinkDied,
+    ProcessDown,
+    Shutdown,
+    DeadLetter,
+}
+
+/// Deadline after which a [`DataMessage`] is not received anymore.
+#[derive(Debug, Clone)]
+pub struct Expiry {
+    pub deadline: Instant,
+    /// If the message expires, an empty data message with the tag is sent to this process.
+    pub notify: Option<(Arc<dyn Process>, i64)>,
+}
+
+/// Counts the bytes of message buffers a process is holding on to.
+///
+/// Message buffers live outside of the linear memory of a process, but are still counted against
+/// its memory limit. A buffer is counted against the process that creates the message until it's
+/// delivered and against the receiving process after that.
+#[derive(Debug, Clone, Default)]
+pub struct MessageMemory {
+    used: Arc<AtomicUsize>,
+}
+
+impl MessageMemory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of bytes used by message buffers.
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+}
+
+/// Bytes of a message buffer counted against a [`MessageMemory`]. They are released once the
+/// message is dropped.
+#[derive(Debug, Default)]
+struct MemoryCharge {
+    memory: Option<MessageMemory>,
+    bytes: usize,
+}
+
+impl MemoryCharge {
+    /// A charge that isn't counted against any process yet.
+    fn unaccounted(bytes: usize) -> Self {
+        Self {
+            memory: None,
+            bytes,
+        }
+    }
+
+    fn set(&mut self, bytes: usize) {
+        if let Some(memory) = self.memory.as_ref() {
+            if bytes > self.bytes {
+                memory.used.fetch_add(bytes - self.bytes, Ordering::Relaxed);
+            } else {
+                memory.used.fetch_sub(self.bytes - bytes, Ordering::Relaxed);
+            }
+        }
+        self.bytes = bytes;
+    }
+
+    fn move_to(&mut self, memory: &MessageMemory) {
+        let bytes = self.bytes;
+        self.set(0);
+        self.memory = Some(memory.clone());
+        self.set(bytes);
+    }
+}
+
+impl Drop for MemoryCharge {
+    fn drop(&mut self) {
+        self.set(0);
+    }
+}
+
+/// An immutable, reference counted buffer that can be attached to many messages without copying
+/// it.
+///
+/// Every process holding on to the buffer counts all of it against its memory limit.
+#[derive(Debug)]
+pub struct SharedBuffer {
+    data: Arc<[u8]>,
+    charge: MemoryCharge,
+}
+
+impl SharedBuffer {
+    pub fn new(data: Arc<[u8]>) -> Self {
+        let charge = MemoryCharge::unaccounted(data.len());
+        Self { data, charge }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Counts the buffer against `memory`, instead of the process that was holding on to it until
+    /// now.
+    pub fn charge_to(&mut self, memory: &MessageMemory) {
+        self.charge.move_to(memory);
+    }
+}
+
+impl Clone for SharedBuffer {
+    /// Returns a new reference to the same data. It's not counted against any process until
+    /// [`SharedBuffer::charge_to`] is called.
+    fn clone(&self) -> Self {
+        Self::new(self.data.clone())
+    }
 }
 
 /// A variant of a [`Message`] that has a buffer of data and resources attached to it.
@@ -43,9 +253,14 @@ impl Message {
 pub struct DataMessage {
     // TODO: Only the Node implementation depends on these fields being public.
     pub tag: Option<i64>,
+    /// Messages with a higher priority are received before the ones with a lower one.
+    pub priority: u32,
+    /// The message is discarded instead of being received after the deadline.
+    pub expiry: Option<Expiry>,
     pub read_ptr: usize,
     pub buffer: Vec<u8>,
     pub resources: Vec<Resource>,
+    charge: MemoryCharge,
 }
 
 impl DataMessage {
@@ -53,12 +268,83 @@ impl DataMessage {
     pub fn new(tag: Option<i64>, buffer_capacity: usize) -> Self {
         Self {
             tag,
+            priority: 0,
+            expiry: None,
             read_ptr: 0,
             buffer: Vec::with_capacity(buffer_capacity),
             resources: Vec::new(),
+            charge: MemoryCharge::unaccounted(buffer_capacity),
         }
     }
 
+    /// Counts the buffer of the message against `memory`, instead of the process that was holding
+    /// on to it until now.
+    pub fn charge_to(&mut self, memory: &MessageMemory) {
+        self.charge.move_to(memory);
+        for resource in self.resources.iter_mut() {
+            if let Resource::SharedBuffer(buffer) = resource {
+                buffer.charge_to(memory);
+            }
+        }
+    }
+
+    /// Returns the number of bytes the buffer and shared buffers of the message count against the
+    /// memory of the process holding on to it.
+    pub fn memory_usage(&self) -> usize {
+        let shared: usize = self
+            .resources
+            .iter()
+            .map(|resource| match resource {
+                Resource::SharedBuffer(buffer) => buffer.charge.bytes,
+                _ => 0,
+            })
+            .sum();
+        self.charge.bytes + shared
+    }
+
+    /// Returns true if the message can be copied with [`try_clone`](Self::try_clone).
+    pub fn is_shareable(&self) -> bool {
+        self.resources.iter().all(|resource| {
+            matches!(
+                resource,
+                Resource::None
+                    | Resource::Process(_)
+                    | Resource::UdpSocket(_)
+                    | Resource::TcpListener(_)
+                    | Resource::SharedBuffer(_)
+            )
+        })
+    }
+
+    /// Returns a copy of the message that can be sent to another process.
+    ///
+    /// Process handles, UDP sockets, TCP listeners and shared buffers are shared between the
+    /// copies. If the message contains any other resource, it can't be copied and `None` is
+    /// returned.
+    pub fn try_clone(&self) -> Option<Self> {
+        let resources = self
+            .resources
+            .iter()
+            .map(|resource| match resource {
+                Resource::None => Some(Resource::None),
+                Resource::Process(process) => Some(Resource::Process(process.clone())),
+                Resource::UdpSocket(socket) => Some(Resource::UdpSocket(socket.clone())),
+                Resource::TcpListener(listener) => Some(Resource::TcpListener(listener.clone())),
+                Resource::SharedBuffer(buffer) => Some(Resource::SharedBuffer(buffer.clone())),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            tag: self.tag,
+            priority: self.priority,
+            expiry: self.expiry.clone(),
+            read_ptr: 0,
+            buffer: self.buffer.clone(),
+            resources,
+            charge: MemoryCharge::unaccounted(self.buffer.len()),
+        })
+    }
+
     /// Adds a process to the message and returns the index of it inside of the message
     pub fn add_process(&mut self, process: Arc<dyn Process>) -> usize {
         self.resources.push(Resource::Process(process));
@@ -77,6 +363,34 @@ impl DataMessage {
         self.resources.len() - 1
     }
 
+    /// Adds a TCP listener to the message and returns the index of it inside of the message
+    pub fn add_tcp_listener(&mut self, tcp_listener: Arc<TcpListener>) -> usize {
+        self.resources.push(Resource::TcpListener(tcp_listener));
+        self.resources.len() - 1
+    }
+
+    /// Adds a shared buffer to the message and returns the index of it inside of the message
+    pub fn add_shared_buffer(&mut self, mut buffer: SharedBuffer) -> usize {
+        if let Some(memory) = self.charge.memory.as_ref() {
+            buffer.charge_to(memory);
+        }
+        self.resources.push(Resource::SharedBuffer(buffer));
+        self.resources.len() - 1
+    }
+
+    /// Adds a compiled module to the message and returns the index of it inside of the message
+    pub fn add_module<M: Any + Send + Sync>(&mut self, module: M) -> usize {
+        self.resources.push(Resource::Module(Box::new(module)));
+        self.resources.len() - 1
+    }
+
+    /// Adds a process configuration to the message and returns the index of it inside of the
+    /// message
+    pub fn add_config<C: Any + Send + Sync>(&mut self, config: C) -> usize {
+        self.resources.push(Resource::Config(Box::new(config)));
+        self.resources.len() - 1
+    }
+
     /// Takes a process from the message, but preserves the indexes of all others.
     ///
     /// If the index is out of bound or the resource is not a process the function will return
@@ -137,6 +451,86 @@ impl DataMessage {
         None
     }
 
+    /// Takes a TCP listener from the message, but preserves the indexes of all others.
+    ///
+    /// If the index is out of bound or the resource is not a tcp listener the function will
+    /// return None.
+    pub fn take_tcp_listener(&mut self, index: usize) -> Option<Arc<TcpListener>> {
+        if let Some(resource_ref) = self.resources.get_mut(index) {
+            let resource = std::mem::replace(resource_ref, Resource::None);
+            match resource {
+                Resource::TcpListener(listener) => {
+                    return Some(listener);
+                }
+                other => {
+                    // Put the resource back if it's not a tcp listener and drop empty.
+                    let _ = std::mem::replace(resource_ref, other);
+                }
+            }
+        }
+        None
+    }
+
+    /// Takes a shared buffer from the message, but preserves the indexes of all others.
+    ///
+    /// If the index is out of bound or the resource is not a shared buffer the function will
+    /// return None.
+    pub fn take_shared_buffer(&mut self, index: usize) -> Option<SharedBuffer> {
+        if let Some(resource_ref) = self.resources.get_mut(index) {
+            let resource = std::mem::replace(resource_ref, Resource::None);
+            match resource {
+                Resource::SharedBuffer(buffer) => {
+                    return Some(buffer);
+                }
+                other => {
+                    // Put the resource back if it's not a shared buffer and drop empty.
+                    let _ = std::mem::replace(resource_ref, other);
+                }
+            }
+        }
+        None
+    }
+
+    /// Takes a compiled module from the message, but preserves the indexes of all others.
+    ///
+    /// If the index is out of bound or the resource is not a module of type `M` the function will
+    /// return None.
+    pub fn take_module<M: Any>(&mut self, index: usize) -> Option<M> {
+        if let Some(resource_ref) = self.resources.get_mut(index) {
+            let resource = std::mem::replace(resource_ref, Resource::None);
+            match resource {
+                Resource::Module(module) if module.is::<M>() => {
+                    return module.downcast().ok().map(|module| *module);
+                }
+                other => {
+                    // Put the resource back if it's not a module and drop empty.
+                    let _ = std::mem::replace(resource_ref, other);
+                }
+            }
+        }
+        None
+    }
+
+    /// Takes a process configuration from the message, but preserves the indexes of all others.
+    ///
+    /// If the index is out of bound or the resource is not a configuration of type `C` the
+    /// function will return None.
+    pub fn take_config<C: Any>(&mut self, index: usize) -> Option<C> {
+        if let Some(resource_ref) = self.resources.get_mut(index) {
+            let resource = std::mem::replace(resource_ref, Resource::None);
+            match resource {
+                Resource::Config(config) if config.is::<C>() => {
+                    return config.downcast().ok().map(|config| *config);
+                }
+                other => {
+                    // Put the resource back if it's not a configuration and drop empty.
+                    let _ = std::mem::replace(resource_ref, other);
+                }
+            }
+        }
+        None
+    }
+
     /// Moves read pointer to index.
     pub fn seek(&mut self, index: usize) {
         self.read_ptr = index;
@@ -150,6 +544,10 @@ impl DataMessage {
 impl Write for DataMessage {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         self.buffer.extend(buf);
+        // The written data is counted, like it's checked against the memory limit, but at least
+        // the capacity requested when the message was created.
+        let charge = self.charge.bytes.max(self.buffer.len());
+        self.charge.set(charge);
         Ok(buf.len())
     }
 
@@ -176,11 +574,19 @@ impl Read for DataMessage {
 
 /// A resource ([`WasmProcess`](crate::WasmProcess), [`TcpStream`](async_std::net::TcpStream),
 /// ...) that is attached to a [`DataMessage`].
+///
+/// The types of modules and configurations depend on the process state, so they are stored type
+/// erased.
 pub enum Resource {
     None,
     Process(Arc<dyn Process>),
     TcpStream(TcpStream),
     UdpSocket(Arc<UdpSocket>),
+    TcpListener(Arc<TcpListener>),
+    /// An immutable buffer that can be attached to many messages without copying it.
+    SharedBuffer(SharedBuffer),
+    Module(Box<dyn Any + Send + Sync>),
+    Config(Box<dyn Any + Send + Sync>),
 }
 
 impl Debug for Resource {
@@ -190,6 +596,12 @@ impl Debug for Resource {
             Self::Process(_) => write!(f, "Process"),
             Self::TcpStream(_) => write!(f, "TcpStream"),
             Self::UdpSocket(_) => write!(f, "UdpSocket"),
+            Self::TcpListener(_) => write!(f, "TcpListener"),
+            Self::SharedBuffer(buffer) => {
+                write!(f, "SharedBuffer({} bytes)", buffer.data().len())
+            }
+            Self::Module(_) => write!(f, "Module"),
+            Self::Config(_) => write!(f, "Config"),
         }
     }
 }
@@ -1,6 +1,9 @@
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 
 use anyhow::Result;
+use uuid::Uuid;
 use wasmtime::ResourceLimiter;
 
 use crate::{
@@ -11,15 +14,61 @@ use crate::{
 
 use super::RawWasm;
 
+// Wasmtime doesn't expose a trap code for running out of fuel, only the error message.
+const OUT_OF_FUEL_MESSAGE: &str = "all fuel consumed by WebAssembly";
+
+/// Defines how running processes are preempted, so that a long running computation can't block
+/// other processes scheduled on the same thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Preemption {
+    /// All processes consume fuel and yield every time they used up a unit of compute.
+    #[default]
+    Fuel,
+    /// A ticker thread advances the epoch of the engine in the given interval and processes yield
+    /// every time the epoch changes. Only processes with a `max_fuel` limit consume fuel.
+    Epoch(Duration),
+}
+
 #[derive(Clone)]
 pub struct WasmtimeRuntime {
     engine: wasmtime::Engine,
+    preemption: Preemption,
+    // With epoch preemption the `engine` doesn't consume fuel. Processes that have a fuel limit
+    // are instantiated inside of this engine instead.
+    fuel_engine: Option<wasmtime::Engine>,
+    _epoch_ticker: Option<Arc<EpochTicker>>,
 }
 
 impl WasmtimeRuntime {
-    pub fn new(config: &wasmtime::Config) -> Result<Self> {
-        let engine = wasmtime::Engine::new(config)?;
-        Ok(Self { engine })
+    /// Creates a new runtime. Fuel consumption and epoch interruption of the `config` are set
+    /// depending on the `preemption`.
+    pub fn new(config: &wasmtime::Config, preemption: Preemption) -> Result<Self> {
+        let mut config = config.clone();
+        match preemption {
+            Preemption::Fuel => config.consume_fuel(true).epoch_interruption(false),
+            Preemption::Epoch(_) => config.consume_fuel(false).epoch_interruption(true),
+        };
+        let engine = wasmtime::Engine::new(&config)?;
+        let (fuel_engine, epoch_ticker) = match preemption {
+            Preemption::Fuel => (None, None),
+            Preemption::Epoch(interval) => {
+                let mut fuel_config = config;
+                fuel_config.epoch_interruption(false).consume_fuel(true);
+                let fuel_engine = wasmtime::Engine::new(&fuel_config)?;
+                let ticker = EpochTicker::start(engine.clone(), interval)?;
+                (Some(fuel_engine), Some(Arc::new(ticker)))
+            }
+        };
+        Ok(Self {
+            engine,
+            preemption,
+            fuel_engine,
+            _epoch_ticker: epoch_ticker,
+        })
+    }
+
+    pub fn preemption(&self) -> Preemption {
+        self.preemption
     }
 
     /// Compiles a wasm module to machine code and performs type-checking on host functions.
@@ -27,18 +76,7 @@ impl WasmtimeRuntime {
     where
         T: ProcessState,
     {
-        let module = wasmtime::Module::new(&self.engine, data.as_slice())?;
-        let mut linker = wasmtime::Linker::new(&self.engine);
-        // Register host functions to linker.
-        <T as ProcessState>::register(&mut linker)?;
-        // The `default_state` and `store` are just used for resolving host functions that are not
-        // owned by any particular `Store`. The "real" instance state and store are created inside
-        // the `instantiate` function.
-        // See: https://docs.rs/wasmtime/latest/wasmtime/struct.Linker.html#method.instantiate_pre
-        // `default_state` should never be accessed and it's safe to use a "fake" state here.
-        let default_state = T::default();
-        let mut store = wasmtime::Store::new(&self.engine, default_state);
-        let instance_pre = linker.instantiate_pre(&mut store, &module)?;
+        let (module, instance_pre) = link(&self.engine, &data)?;
         let compiled_module = WasmtimeCompiledModule::new(data, module, instance_pre);
         Ok(compiled_module)
     }
@@ -52,38 +90,108 @@ impl WasmtimeRuntime {
         T: ProcessState + Send + ResourceLimiter,
     {
         let max_fuel = state.config().get_max_fuel();
-        let mut store = wasmtime::Store::new(&self.engine, state);
-        // Set limits of the store
-        store.limiter(|state| state);
-        // Trap if out of fuel
-        store.out_of_fuel_trap();
-        // Define maximum fuel
-        match max_fuel {
-            Some(max_fuel) => {
-                store.out_of_fuel_async_yield(max_fuel, UNIT_OF_COMPUTE_IN_INSTRUCTIONS)
+        let (mut store, instance_pre, consumes_fuel) = match (&self.fuel_engine, max_fuel) {
+            // Epoch preemption, only processes with a fuel limit need to consume fuel.
+            (Some(fuel_engine), Some(_)) => {
+                let instance_pre = compiled_module.fuel_instantiator(fuel_engine)?;
+                (wasmtime::Store::new(fuel_engine, state), instance_pre, true)
+            }
+            (Some(_), None) => {
+                let mut store = wasmtime::Store::new(&self.engine, state);
+                // Yield every time the epoch changes
+                store.epoch_deadline_async_yield_and_update(1);
+                (store, compiled_module.instantiator().clone(), false)
             }
-            // If no limit is specified use maximum
-            None => store.out_of_fuel_async_yield(u64::MAX, UNIT_OF_COMPUTE_IN_INSTRUCTIONS),
+            (None, _) => (
+                wasmtime::Store::new(&self.engine, state),
+                compiled_module.instantiator().clone(),
+                true,
+            ),
         };
+        // Set limits of the store
+        store.limiter(|state| state);
+        if consumes_fuel {
+            // Trap if out of fuel
+            store.out_of_fuel_trap();
+            // Define maximum fuel
+            match max_fuel {
+                Some(max_fuel) => {
+                    store.out_of_fuel_async_yield(max_fuel, UNIT_OF_COMPUTE_IN_INSTRUCTIONS)
+                }
+                // If no limit is specified use maximum
+                None => store.out_of_fuel_async_yield(u64::MAX, UNIT_OF_COMPUTE_IN_INSTRUCTIONS),
+            };
+        }
         // Create instance
-        let instance = compiled_module
-            .instantiator()
-            .instantiate_async(&mut store)
-            .await?;
+        let instance = instance_pre.instantiate_async(&mut store).await?;
         // Mark state as initialized
         store.data_mut().initialize();
         Ok(WasmtimeInstance { store, instance })
     }
 }
 
+/// Advances the epoch of an engine in a regular interval, until it's dropped.
+struct EpochTicker {
+    stop: Arc<AtomicBool>,
+}
+
+impl EpochTicker {
+    fn start(engine: wasmtime::Engine, interval: Duration) -> Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_ticker = stop.clone();
+        std::thread::Builder::new()
+            .name("lunatic-epoch-ticker".to_owned())
+            .spawn(move || {
+                while !stop_ticker.load(Ordering::Relaxed) {
+                    std::thread::sleep(interval);
+                    engine.increment_epoch();
+                }
+            })?;
+        Ok(Self { stop })
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+// Compiles the module inside of the `engine` and links the host functions to it.
+fn link<T>(
+    engine: &wasmtime::Engine,
+    data: &RawWasm,
+) -> Result<(wasmtime::Module, wasmtime::InstancePre<T>)>
+where
+    T: ProcessState,
+{
+    let module = wasmtime::Module::new(engine, data.as_slice())?;
+    let mut linker = wasmtime::Linker::new(engine);
+    // Register host functions to linker.
+    <T as ProcessState>::register(&mut linker)?;
+    // The `default_state` and `store` are just used for resolving host functions that are not
+    // owned by any particular `Store`. The "real" instance state and store are created inside
+    // the `instantiate` function.
+    // See: https://docs.rs/wasmtime/latest/wasmtime/struct.Linker.html#method.instantiate_pre
+    // `default_state` should never be accessed and it's safe to use a "fake" state here.
+    let default_state = T::default();
+    let mut store = wasmtime::Store::new(engine, default_state);
+    let instance_pre = linker.instantiate_pre(&mut store, &module)?;
+    Ok((module, instance_pre))
+}
+
 pub struct WasmtimeCompiledModule<T> {
     inner: Arc<WasmtimeCompiledModuleInner<T>>,
 }
 
 pub struct WasmtimeCompiledModuleInner<T> {
+    id: Uuid,
     source: RawWasm,
     module: wasmtime::Module,
     instance_pre: wasmtime::InstancePre<T>,
+    // The module compiled for the fuel engine of a runtime with epoch preemption. It's only
+    // compiled once a process with a fuel limit is spawned from it.
+    fuel_instance_pre: OnceLock<wasmtime::InstancePre<T>>,
 }
 
 impl<T> WasmtimeCompiledModule<T> {
@@ -93,13 +201,20 @@ impl<T> WasmtimeCompiledModule<T> {
         instance_pre: wasmtime::InstancePre<T>,
     ) -> WasmtimeCompiledModule<T> {
         let inner = Arc::new(WasmtimeCompiledModuleInner {
+            id: Uuid::new_v4(),
             source,
             module,
             instance_pre,
+            fuel_instance_pre: OnceLock::new(),
         });
         Self { inner }
     }
 
+    /// Returns the unique ID of the compiled module.
+    pub fn id(&self) -> Uuid {
+        self.inner.id
+    }
+
     pub fn exports(&self) -> impl ExactSizeIterator<Item = wasmtime::ExportType<'_>> {
         self.inner.module.exports()
     }
@@ -111,6 +226,24 @@ impl<T> WasmtimeCompiledModule<T> {
     pub fn instantiator(&self) -> &wasmtime::InstancePre<T> {
         &self.inner.instance_pre
     }
+
+    /// Returns the instantiator for the fuel engine of a runtime with epoch preemption, compiling
+    /// the module for it the first time it's used.
+    fn fuel_instantiator(&self, fuel_engine: &wasmtime::Engine) -> Result<wasmtime::InstancePre<T>>
+    where
+        T: ProcessState,
+    {
+        if let Some(instance_pre) = self.inner.fuel_instance_pre.get() {
+            return Ok(instance_pre.clone());
+        }
+        // No lock is held while compiling. If two processes race, the first result is kept.
+        let (_, instance_pre) = link(fuel_engine, &self.inner.source)?;
+        Ok(self
+            .inner
+            .fuel_instance_pre
+            .get_or_init(|| instance_pre)
+            .clone())
+    }
 }
 
 impl<T> Clone for WasmtimeCompiledModule<T> {
@@ -131,7 +264,7 @@ where
 
 impl<T> WasmtimeInstance<T>
 where
-    T: Send,
+    T: ProcessState + Send,
 {
     pub async fn call(mut self, function: &str, params: Vec<wasmtime::Val>) -> ExecutionResult<T> {
         let entry = self.instance.get_func(&mut self.store, function);
@@ -148,39 +281,46 @@ where
             .call_async(&mut self.store, &params, &mut [])
             .await;
 
-        ExecutionResult {
-            state: self.store.into_data(),
-            result: match result {
-                Ok(()) => ResultValue::Ok,
-                Err(err) => {
-                    // If the trap is a result of calling `proc_exit(0)`, treat it as an no-error finish.
-                    match err.downcast_ref::<wasmtime::Trap>() {
-                        Some(trap) => {
-                            if trap.i32_exit_status().is_some()
-                                && trap.i32_exit_status().unwrap() == 0
-                            {
-                                ResultValue::Ok
-                            } else {
-                                ResultValue::Failed(trap.to_string())
-                            }
-                        }
-                        None => {
-                            ResultValue::Failed("Can't downcast trap to wasmtime::Trap".to_string())
+        let result = match result {
+            Ok(()) => ResultValue::Ok,
+            Err(err) => {
+                // If the trap is a result of calling `proc_exit(0)`, treat it as an no-error finish.
+                match err.downcast_ref::<wasmtime::Trap>() {
+                    Some(trap) => {
+                        if trap.i32_exit_status().is_some() && trap.i32_exit_status().unwrap() == 0
+                        {
+                            ResultValue::Ok
+                        } else if trap.to_string().contains(OUT_OF_FUEL_MESSAGE) {
+                            ResultValue::FuelExhausted(trap.to_string())
+                        } else if self.store.data().memory_limit_reached() {
+                            // A failed memory allocation usually surfaces as an unrelated trap
+                            // (e.g. `unreachable`) inside the guest's allocator.
+                            ResultValue::MemoryLimit(trap.to_string())
+                        } else {
+                            ResultValue::Failed(trap.to_string())
                         }
                     }
+                    None => {
+                        ResultValue::Failed("Can't downcast trap to wasmtime::Trap".to_string())
+                    }
                 }
-            },
+            }
+        };
+
+        ExecutionResult {
+            state: self.store.into_data(),
+            result,
         }
     }
 }
 
+/// Returns the wasmtime configuration used by lunatic. Fuel consumption and epoch interruption are
+/// set by [`WasmtimeRuntime::new`] depending on the preemption mode.
 pub fn default_config() -> wasmtime::Config {
     let mut config = wasmtime::Config::new();
     config
         .async_support(true)
         .debug_info(false)
-        // The behavior of fuel running out is defined on the Store
-        .consume_fuel(true)
         .wasm_reference_types(true)
         .wasm_bulk_memory(true)
         .wasm_multi_value(true)
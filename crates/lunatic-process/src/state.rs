@@ -9,8 +9,10 @@ use wasmtime::Linker;
 
 use crate::{
     config::ProcessConfig,
+    info::SharedProcessInfo,
     mailbox::MessageMailbox,
     runtimes::wasmtime::{WasmtimeCompiledModule, WasmtimeRuntime},
+    table::ProcessTable,
     Process, Signal,
 };
 
@@ -22,7 +24,7 @@ pub type ConfigResources<T> = HashMapId<T>;
 /// - It holds onto all vm resources (file descriptors, tcp streams, channels, ...)
 /// - Registers all host functions working on those resources to the `Linker`
 pub trait ProcessState: Sized + Default {
-    type Config: ProcessConfig + Default + Send + Sync;
+    type Config: ProcessConfig + Default + Send + Sync + 'static;
 
     // Create a new `ProcessState`
     fn new(
@@ -30,6 +32,7 @@ pub trait ProcessState: Sized + Default {
         module: WasmtimeCompiledModule<Self>,
         config: Arc<Self::Config>,
         registry: Arc<DashMap<String, Arc<dyn Process>>>,
+        process_table: ProcessTable,
     ) -> Result<Self>;
 
     /// Register all host functions to the linker.
@@ -45,6 +48,10 @@ pub trait ProcessState: Sized + Default {
     fn module(&self) -> &WasmtimeCompiledModule<Self>;
     /// Returns the process configuration
     fn config(&self) -> &Arc<Self::Config>;
+    /// Returns true if a memory allocation was denied because of the configured memory limit
+    fn memory_limit_reached(&self) -> bool;
+    /// Marks that a memory allocation was denied because of the configured memory limit
+    fn set_memory_limit_reached(&mut self);
 
     // Returns ID
     fn id(&self) -> Uuid;
@@ -52,6 +59,8 @@ pub trait ProcessState: Sized + Default {
     fn signal_mailbox(&self) -> &(Sender<Signal>, Receiver<Signal>);
     // Returns message mailbox
     fn message_mailbox(&self) -> &MessageMailbox;
+    // Returns the shared process information
+    fn process_info(&self) -> &SharedProcessInfo;
 
     // Config resources
     fn config_resources(&self) -> &ConfigResources<Self::Config>;
@@ -59,4 +68,7 @@ pub trait ProcessState: Sized + Default {
 
     // Registry
     fn registry(&self) -> &Arc<DashMap<String, Arc<dyn Process>>>;
+
+    // Table of all live processes
+    fn process_table(&self) -> &ProcessTable;
 }
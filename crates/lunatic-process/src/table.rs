@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use uuid::Uuid;
+
+use crate::{dead_letters::DeadLetters, groups::ProcessGroups, Process};
+
+/// An entry of the [`ProcessTable`].
+#[derive(Debug, Clone)]
+pub struct ProcessEntry {
+    pub process: Arc<dyn Process>,
+    /// ID of the module the process was spawned from.
+    pub module_id: Uuid,
+    /// ID of the process that spawned this one. Processes spawned by the runtime don't have one.
+    pub parent: Option<Uuid>,
+}
+
+/// The `ProcessTable` keeps track of all live processes of a runtime.
+///
+/// Processes are added to the table when they are spawned and removed once they finish. This
+/// allows reaching any process by its ID, even if all handles to it were dropped.
+///
+/// Messages sent to processes that already left the table end up in its [`DeadLetters`]. Processes
+/// leaving the table also leave all their [`ProcessGroups`].
+#[derive(Clone, Default)]
+pub struct ProcessTable {
+    inner: Arc<DashMap<Uuid, ProcessEntry>>,
+    dead_letters: DeadLetters,
+    groups: ProcessGroups,
+}
+
+impl ProcessTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the dead letters shared by all processes of the runtime.
+    pub fn dead_letters(&self) -> &DeadLetters {
+        &self.dead_letters
+    }
+
+    /// Returns the process groups shared by all processes of the runtime.
+    pub fn groups(&self) -> &ProcessGroups {
+        &self.groups
+    }
+
+    /// Adds a process to the table.
+    pub fn insert(&self, entry: ProcessEntry) {
+        self.inner.insert(entry.process.id(), entry);
+    }
+
+    /// Adds the process to the group `name` and returns true, if it's still in the table.
+    pub fn join_group(&self, name: &str, process: Arc<dyn Process>) -> bool {
+        // Holding on to the entry blocks `remove` until the process joined, so that leaving all
+        // groups afterwards can't miss this one.
+        match self.inner.get(&process.id()) {
+            Some(_entry) => {
+                self.groups.join(name, process);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the process with the given ID from the table and all groups.
+    pub fn remove(&self, id: Uuid) -> Option<ProcessEntry> {
+        let entry = self.inner.remove(&id).map(|(_, entry)| entry);
+        self.groups.leave_all(id);
+        entry
+    }
+
+    /// Returns the process with the given ID, if it's still alive.
+    pub fn get(&self, id: Uuid) -> Option<Arc<dyn Process>> {
+        self.inner.get(&id).map(|entry| entry.process.clone())
+    }
+
+    /// Returns the table entry of the process with the given ID, if it's still alive.
+    pub fn entry(&self, id: Uuid) -> Option<ProcessEntry> {
+        self.inner.get(&id).map(|entry| entry.clone())
+    }
+
+    /// Returns the number of live processes.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns true if there are no live processes.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Returns all live processes.
+    pub fn list(&self) -> Vec<ProcessEntry> {
+        self.filter(|_| true)
+    }
+
+    /// Returns all live processes matching the `predicate`.
+    pub fn filter<F>(&self, predicate: F) -> Vec<ProcessEntry>
+    where
+        F: Fn(&ProcessEntry) -> bool,
+    {
+        self.inner
+            .iter()
+            .filter(|entry| predicate(entry.value()))
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    /// Returns all live processes spawned from the module with the given ID.
+    pub fn by_module(&self, module_id: Uuid) -> Vec<ProcessEntry> {
+        self.filter(|entry| entry.module_id == module_id)
+    }
+
+    /// Returns all live processes spawned by the process with the given ID.
+    pub fn by_parent(&self, parent: Uuid) -> Vec<ProcessEntry> {
+        self.filter(|entry| entry.parent == Some(parent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use uuid::Uuid;
+
+    use super::{ProcessEntry, ProcessTable};
+    use crate::{test_helpers::idle, Process};
+
+    #[async_std::test]
+    async fn filter_by_module_and_parent() {
+        let table = ProcessTable::new();
+        let module_a = Uuid::new_v4();
+        let module_b = Uuid::new_v4();
+        let mut processes = Vec::new();
+        for _ in 0..3 {
+            let (_, process) = idle();
+            processes.push(process);
+        }
+        let parent = processes[0].id();
+        table.insert(ProcessEntry {
+            process: Arc::new(processes[0].clone()),
+            module_id: module_a,
+            parent: None,
+        });
+        table.insert(ProcessEntry {
+            process: Arc::new(processes[1].clone()),
+            module_id: module_a,
+            parent: Some(parent),
+        });
+        table.insert(ProcessEntry {
+            process: Arc::new(processes[2].clone()),
+            module_id: module_b,
+            parent: Some(parent),
+        });
+
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.by_module(module_a).len(), 2);
+        assert_eq!(table.by_module(module_b).len(), 1);
+        assert_eq!(table.by_parent(parent).len(), 2);
+        assert_eq!(
+            table.get(processes[1].id()).map(|process| process.id()),
+            Some(processes[1].id())
+        );
+
+        table.remove(processes[1].id());
+        assert!(table.get(processes[1].id()).is_none());
+        assert_eq!(table.by_parent(parent).len(), 1);
+    }
+
+    #[async_std::test]
+    async fn only_processes_in_the_table_join_groups() {
+        let table = ProcessTable::new();
+        let (_, process) = idle();
+        assert!(!table.join_group("a", Arc::new(process.clone())));
+        assert!(table.groups().members("a").is_empty());
+
+        table.insert(ProcessEntry {
+            process: Arc::new(process.clone()),
+            module_id: Uuid::new_v4(),
+            parent: None,
+        });
+        assert!(table.join_group("a", Arc::new(process.clone())));
+        assert_eq!(table.groups().members("a").len(), 1);
+
+        table.remove(process.id());
+        assert!(table.groups().members("a").is_empty());
+        assert!(!table.join_group("a", Arc::new(process.clone())));
+        assert!(table.groups().members("a").is_empty());
+    }
+}
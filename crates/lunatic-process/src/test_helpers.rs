@@ -0,0 +1,31 @@
+// Processes shared by the tests of this crate.
+
+use anyhow::Result;
+use async_std::task::JoinHandle;
+
+use crate::{
+    dead_letters::DeadLetters, message::Message, spawn, ExecutionResult, NativeProcess, ResultValue,
+};
+
+// Spawns a process that finishes with the first message it receives, e.g. a `ProcessDown`
+// notification of a process it monitors.
+pub(crate) fn watcher() -> (JoinHandle<Result<Message>>, NativeProcess) {
+    spawn(DeadLetters::default(), |_, mailbox| async move {
+        let message = mailbox.pop(None).await;
+        ExecutionResult {
+            state: message,
+            result: ResultValue::Ok,
+        }
+    })
+}
+
+// Spawns a process that waits until it receives any message and finishes normally after that.
+pub(crate) fn idle() -> (JoinHandle<Result<()>>, NativeProcess) {
+    spawn(DeadLetters::default(), |_, mailbox| async move {
+        mailbox.pop(None).await;
+        ExecutionResult {
+            state: (),
+            result: ResultValue::Ok,
+        }
+    })
+}
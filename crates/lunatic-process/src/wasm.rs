@@ -5,9 +5,11 @@ use async_std::task::JoinHandle;
 use log::trace;
 use wasmtime::{ResourceLimiter, Val};
 
+use crate::config::ProcessConfig;
 use crate::runtimes::wasmtime::{WasmtimeCompiledModule, WasmtimeRuntime};
 use crate::state::ProcessState;
-use crate::{Process, Signal, WasmProcess};
+use crate::table::ProcessEntry;
+use crate::{Process, Signal, TimeLimits, WasmProcess};
 
 /// Spawns a new wasm process from a compiled module.
 ///
@@ -18,6 +20,10 @@ use crate::{Process, Signal, WasmProcess};
 /// After it's spawned the process will keep running in the background. A process can be killed
 /// with `Signal::Kill` signal. If you would like to block until the process is finished you can
 /// `.await` on the returned `JoinHandle<()>`.
+///
+/// While running, the process is part of the runtime's process table. **parent** is the process
+/// that spawned it, if there is one. The parent is notified when the child is spawned and when it
+/// finishes, so that it can keep track of its children. This doesn't link the processes.
 pub async fn spawn_wasm<S>(
     runtime: WasmtimeRuntime,
     module: WasmtimeCompiledModule<S>,
@@ -25,6 +31,7 @@ pub async fn spawn_wasm<S>(
     function: &str,
     params: Vec<Val>,
     link: Option<(Option<i64>, Arc<dyn Process>)>,
+    parent: Option<Arc<dyn Process>>,
 ) -> Result<(JoinHandle<Result<S>>, Arc<dyn Process>)>
 where
     S: ProcessState + Send + ResourceLimiter + 'static,
@@ -34,12 +41,34 @@ where
 
     let signal_mailbox = state.signal_mailbox().clone();
     let message_mailbox = state.message_mailbox().clone();
+    let info = state.process_info().clone();
+    let process_table = state.process_table().clone();
+    let module_id = module.id();
+    let parent_id = parent.as_ref().map(|parent| parent.id());
+    info.set_parent(parent_id);
+    let time_limits = TimeLimits {
+        execution_time: state.config().get_max_execution_time(),
+        cpu_time: state.config().get_max_cpu_time(),
+    };
 
     let instance = runtime.instantiate(&module, state).await?;
     let function = function.to_string();
     let fut = async move { instance.call(&function, params).await };
-    let child_process = crate::new(fut, id, signal_mailbox.1, message_mailbox);
-    let child_process_handle = WasmProcess::new(id, signal_mailbox.0.clone());
+    let child_process = crate::new(
+        fut,
+        id,
+        signal_mailbox.1,
+        message_mailbox,
+        info.clone(),
+        process_table.dead_letters().clone(),
+        time_limits,
+    );
+    let child_process_handle = WasmProcess::new(
+        id,
+        signal_mailbox.0.clone(),
+        info,
+        process_table.dead_letters().clone(),
+    );
 
     // **Child link guarantees**:
     // The link signal is going to be put inside of the child's mailbox and is going to be
@@ -66,16 +95,40 @@ where
     //       running somewhere else.
     if let Some((tag, process)) = link {
         // Send signal to itself to perform the linking
-        process.send(Signal::Link(None, Arc::new(child_process_handle.clone())));
+        process.send(Signal::Link(
+            None,
+            Arc::new(child_process_handle.clone()),
+            false,
+        ));
         // Suspend itself to process all new signals
         async_std::task::yield_now().await;
         // Send signal to child to link it
         signal_mailbox
             .0
-            .try_send(Signal::Link(tag, process))
+            .try_send(Signal::Link(tag, process, false))
             .expect("receiver must exist at this point");
     }
 
+    // Keep the process in the process table for as long as it's running
+    process_table.insert(ProcessEntry {
+        process: Arc::new(child_process_handle.clone()),
+        module_id,
+        parent: parent_id,
+    });
+    // The `ChildSpawned` signal is always sent before the `ChildFinished` one, so the parent
+    // can't end up with a finished child in its list.
+    if let Some(parent) = parent.as_ref() {
+        parent.send(Signal::ChildSpawned(id));
+    }
+    let child_process = async move {
+        let result = child_process.await;
+        process_table.remove(id);
+        if let Some(parent) = parent {
+            parent.send(Signal::ChildFinished(id));
+        }
+        result
+    };
+
     // Spawn a background process
     trace!("Process size: {}", std::mem::size_of_val(&child_process));
     let join = async_std::task::spawn(child_process);
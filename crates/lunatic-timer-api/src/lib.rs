@@ -0,0 +1,241 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use async_std::task::JoinHandle;
+use hash_map_id::HashMapId;
+use lunatic_common_api::IntoTrap;
+use lunatic_process::{state::ProcessState, Signal};
+use lunatic_process_api::ProcessCtx;
+use wasmtime::{Caller, Linker, Trap};
+
+/// Maximum number of timers a process can have waiting at the same time.
+pub const MAX_TIMERS: usize = 10_000;
+
+/// Timers that are waiting to fire.
+///
+/// A timer removes itself once it fires, so that timers that are never canceled don't pile up.
+/// Timers belong to the process that created them, once the last handle is dropped all pending
+/// timers are canceled and will never fire.
+#[derive(Clone, Default, Debug)]
+pub struct TimerResources {
+    inner: Arc<Timers>,
+}
+
+#[derive(Default, Debug)]
+struct Timers {
+    pending: Mutex<HashMapId<Option<JoinHandle<()>>>>,
+}
+
+impl Drop for Timers {
+    fn drop(&mut self) {
+        let pending = self.pending.get_mut().expect("never poisoned");
+        // Dropping a `JoinHandle` only detaches the task, it needs to be canceled explicitly.
+        for handle in pending.drain().flatten() {
+            async_std::task::spawn(handle.cancel());
+        }
+    }
+}
+
+impl TimerResources {
+    /// Runs `fire` after `delay` and returns the ID of the timer.
+    ///
+    /// Returns `None` if there are already [`MAX_TIMERS`] timers waiting.
+    pub fn add<F>(&self, delay: Duration, fire: F) -> Option<u64>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // The lock is held until the handle is stored, so that a timer firing right away can't
+        // try to remove itself before it was added.
+        let mut pending = self.inner.pending.lock().expect("never poisoned");
+        if pending.len() >= MAX_TIMERS {
+            return None;
+        }
+        let id = pending.add(None);
+        // Only a weak reference is held, so that waiting timers don't keep their owner's timers
+        // alive.
+        let timers = Arc::downgrade(&self.inner);
+        let handle = async_std::task::spawn(async move {
+            async_std::task::sleep(delay).await;
+            // If the owner is gone the timer was canceled
+            if let Some(timers) = timers.upgrade() {
+                // The timer is removed before firing, so that it can't be canceled afterwards.
+                let pending = timers.pending.lock().expect("never poisoned").remove(id);
+                if pending.is_some() {
+                    fire();
+                }
+            }
+        });
+        *pending.get_mut(id).expect("just added") = Some(handle);
+        Some(id)
+    }
+
+    /// Returns the number of timers that are waiting to fire.
+    pub fn len(&self) -> usize {
+        self.inner.pending.lock().expect("never poisoned").len()
+    }
+
+    /// Returns true if no timers are waiting to fire.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Cancels the timer and returns true if it already fired or doesn't exist.
+    pub async fn cancel(&self, id: u64) -> bool {
+        let handle = self
+            .inner
+            .pending
+            .lock()
+            .expect("never poisoned")
+            .remove(id);
+        match handle {
+            // A timer that is still pending didn't fire, because it removes itself first.
+            Some(Some(handle)) => {
+                handle.cancel().await;
+                false
+            }
+            _ => true,
+        }
+    }
+}
+
+pub trait TimerCtx {
+    fn timer_resources(&self) -> &TimerResources;
+}
+
+// Register the timer APIs to the linker
+pub fn register<T: ProcessState + ProcessCtx<T> + TimerCtx + Send + 'static>(
+    linker: &mut Linker<T>,
+) -> Result<()> {
+    linker.func_wrap("lunatic::timer", "send_after", send_after)?;
+    linker.func_wrap1_async("lunatic::timer", "cancel_timer", cancel_timer)?;
+    Ok(())
+}
+
+// Sends the message in the scratch area to **process_id** after **delay_ms** milliseconds and
+// returns the ID of the timer. The process doesn't block while waiting on the timer. Timers that
+// didn't fire yet are canceled when the process finishes.
+//
+// Traps:
+// * If the process ID doesn't exist.
+// * If it's called before creating the next message.
+// * If the process already has `MAX_TIMERS` timers waiting to fire.
+fn send_after<T: ProcessState + ProcessCtx<T> + TimerCtx>(
+    mut caller: Caller<T>,
+    process_id: u64,
+    delay_ms: u64,
+) -> Result<u64, Trap> {
+    let message = caller
+        .data_mut()
+        .message_scratch_area()
+        .take()
+        .or_trap("lunatic::timer::send_after")?;
+    let process = caller
+        .data()
+        .process_resources()
+        .get(process_id)
+        .or_trap("lunatic::timer::send_after")?
+        .clone();
+    let timer_id = caller
+        .data()
+        .timer_resources()
+        .add(Duration::from_millis(delay_ms), move || {
+            process.send(Signal::Message(message))
+        })
+        .or_trap("lunatic::timer::send_after: too many timers")?;
+    Ok(timer_id)
+}
+
+// Cancels the timer **timer_id**.
+//
+// Returns:
+// * 0 if the timer was canceled before it fired.
+// * 1 if the timer already fired or doesn't exist.
+fn cancel_timer<T: ProcessState + ProcessCtx<T> + TimerCtx + Send>(
+    caller: Caller<T>,
+    timer_id: u64,
+) -> Box<dyn Future<Output = u32> + Send + '_> {
+    let timers = caller.data().timer_resources().clone();
+    Box::new(async move { timers.cancel(timer_id).await as u32 })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_std::channel::unbounded;
+
+    use super::{TimerResources, MAX_TIMERS};
+
+    #[async_std::test]
+    async fn timer_fires_after_delay() {
+        let timers = TimerResources::default();
+        let (sender, receiver) = unbounded();
+        timers
+            .add(Duration::from_millis(10), move || {
+                sender.try_send(()).unwrap()
+            })
+            .unwrap();
+        receiver.recv().await.unwrap();
+        // Fired timers remove themselves
+        async_std::task::sleep(Duration::from_millis(10)).await;
+        assert!(timers.is_empty());
+    }
+
+    #[async_std::test]
+    async fn canceled_timer_does_not_fire() {
+        let timers = TimerResources::default();
+        let (sender, receiver) = unbounded();
+        let id = timers
+            .add(Duration::from_millis(50), move || {
+                sender.try_send(()).unwrap()
+            })
+            .unwrap();
+        assert!(!timers.cancel(id).await);
+        async_std::task::sleep(Duration::from_millis(100)).await;
+        assert!(receiver.try_recv().is_err());
+        assert!(timers.is_empty());
+    }
+
+    #[async_std::test]
+    async fn cancel_after_timer_fired() {
+        let timers = TimerResources::default();
+        let (sender, receiver) = unbounded();
+        let id = timers
+            .add(Duration::from_millis(1), move || {
+                sender.try_send(()).unwrap()
+            })
+            .unwrap();
+        receiver.recv().await.unwrap();
+        assert!(timers.cancel(id).await);
+        // Unknown timers are reported as fired
+        assert!(timers.cancel(id + 1).await);
+    }
+
+    #[async_std::test]
+    async fn timers_are_canceled_when_dropped() {
+        let timers = TimerResources::default();
+        let (sender, receiver) = unbounded();
+        timers
+            .add(Duration::from_millis(50), move || {
+                sender.try_send(()).unwrap()
+            })
+            .unwrap();
+        drop(timers);
+        async_std::task::sleep(Duration::from_millis(100)).await;
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[async_std::test]
+    async fn number_of_timers_is_limited() {
+        let timers = TimerResources::default();
+        for _ in 0..MAX_TIMERS {
+            assert!(timers.add(Duration::from_secs(60), || ()).is_some());
+        }
+        assert!(timers.add(Duration::from_secs(60), || ()).is_none());
+        assert_eq!(timers.len(), MAX_TIMERS);
+    }
+}
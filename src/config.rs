@@ -1,6 +1,8 @@
 use std::fmt::Debug;
+use std::time::Duration;
 
 use lunatic_process::config::ProcessConfig;
+use lunatic_process::mailbox::OverflowPolicy;
 use lunatic_process_api::ProcessConfigCtx;
 use lunatic_wasi_api::LunaticWasiConfigCtx;
 use serde::{Deserialize, Serialize};
@@ -11,12 +13,24 @@ pub struct DefaultProcessConfig {
     max_memory: usize,
     // Maximum amount of compute expressed in units of 100k instructions.
     max_fuel: Option<u64>,
+    // Maximum number of messages waiting in the mailbox
+    mailbox_capacity: Option<usize>,
+    // What happens if a message arrives at a full mailbox
+    mailbox_overflow_policy: OverflowPolicy,
+    // Maximum size of a message buffer in bytes
+    max_message_size: Option<usize>,
+    // Maximum wall-clock time the process can run
+    max_execution_time: Option<Duration>,
+    // Maximum time the process can spend running between yields
+    max_cpu_time: Option<Duration>,
     // Can this process compile new WebAssembly modules
     can_compile_modules: bool,
     // Can this process create new configurations
     can_create_configs: bool,
     // Can this process spawn sub-processes
     can_spawn_processes: bool,
+    // Can this process list and look up all processes of the runtime
+    can_inspect_processes: bool,
     // WASI configs
     preopened_dirs: Vec<String>,
     command_line_arguments: Vec<String>,
@@ -28,6 +42,11 @@ impl Debug for DefaultProcessConfig {
         f.debug_struct("EnvConfig")
             .field("max_memory", &self.max_memory)
             .field("max_fuel", &self.max_fuel)
+            .field("mailbox_capacity", &self.mailbox_capacity)
+            .field("mailbox_overflow_policy", &self.mailbox_overflow_policy)
+            .field("max_message_size", &self.max_message_size)
+            .field("max_execution_time", &self.max_execution_time)
+            .field("max_cpu_time", &self.max_cpu_time)
             .field("preopened_dirs", &self.preopened_dirs)
             .field("args", &self.command_line_arguments)
             .field("envs", &self.environment_variables)
@@ -51,6 +70,46 @@ impl ProcessConfig for DefaultProcessConfig {
     fn get_max_memory(&self) -> usize {
         self.max_memory
     }
+
+    fn set_mailbox_capacity(&mut self, capacity: Option<usize>) {
+        self.mailbox_capacity = capacity
+    }
+
+    fn get_mailbox_capacity(&self) -> Option<usize> {
+        self.mailbox_capacity
+    }
+
+    fn set_mailbox_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self.mailbox_overflow_policy = policy
+    }
+
+    fn get_mailbox_overflow_policy(&self) -> OverflowPolicy {
+        self.mailbox_overflow_policy
+    }
+
+    fn set_max_message_size(&mut self, max_message_size: Option<usize>) {
+        self.max_message_size = max_message_size
+    }
+
+    fn get_max_message_size(&self) -> Option<usize> {
+        self.max_message_size
+    }
+
+    fn set_max_execution_time(&mut self, max_execution_time: Option<Duration>) {
+        self.max_execution_time = max_execution_time
+    }
+
+    fn get_max_execution_time(&self) -> Option<Duration> {
+        self.max_execution_time
+    }
+
+    fn set_max_cpu_time(&mut self, max_cpu_time: Option<Duration>) {
+        self.max_cpu_time = max_cpu_time
+    }
+
+    fn get_max_cpu_time(&self) -> Option<Duration> {
+        self.max_cpu_time
+    }
 }
 
 impl LunaticWasiConfigCtx for DefaultProcessConfig {
@@ -118,6 +177,14 @@ impl ProcessConfigCtx for DefaultProcessConfig {
     fn set_can_spawn_processes(&mut self, can: bool) {
         self.can_spawn_processes = can
     }
+
+    fn can_inspect_processes(&self) -> bool {
+        self.can_inspect_processes
+    }
+
+    fn set_can_inspect_processes(&mut self, can: bool) {
+        self.can_inspect_processes = can
+    }
 }
 
 impl Default for DefaultProcessConfig {
@@ -125,9 +192,15 @@ impl Default for DefaultProcessConfig {
         Self {
             max_memory: u32::MAX as usize, // = 4 GB
             max_fuel: None,
+            mailbox_capacity: None,
+            mailbox_overflow_policy: OverflowPolicy::default(),
+            max_message_size: None,
+            max_execution_time: None,
+            max_cpu_time: None,
             can_compile_modules: false,
             can_create_configs: false,
             can_spawn_processes: false,
+            can_inspect_processes: false,
             preopened_dirs: vec![],
             command_line_arguments: vec![],
             environment_variables: vec![],
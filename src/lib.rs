@@ -34,5 +34,13 @@ mod config;
 mod state;
 
 pub use config::DefaultProcessConfig;
-pub use lunatic_process::{spawn, wasm::spawn_wasm, Finished, Process, Signal, WasmProcess};
+pub use lunatic_process::{
+    dead_letters::{DeadLetterSink, DeadLetterStats, DeadLetters},
+    groups::ProcessGroups,
+    info::{ProcessInfo, ProcessStatus},
+    spawn,
+    table::{ProcessEntry, ProcessTable},
+    wasm::spawn_wasm,
+    ExitReason, Finished, Process, Signal, WasmProcess,
+};
 pub use state::DefaultProcessState;
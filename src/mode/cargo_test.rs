@@ -4,7 +4,7 @@ use anyhow::{Context, Result};
 use clap::{crate_version, Arg, Command};
 
 use dashmap::DashMap;
-use lunatic_process::{runtimes, state::ProcessState};
+use lunatic_process::{runtimes, state::ProcessState, table::ProcessTable};
 use lunatic_process_api::ProcessConfigCtx;
 use lunatic_runtime::{spawn_wasm, DefaultProcessConfig, DefaultProcessState};
 use lunatic_stdout_capture::StdoutCapture;
@@ -73,6 +73,7 @@ pub(crate) async fn test() -> Result<()> {
     config.set_can_compile_modules(true);
     config.set_can_create_configs(true);
     config.set_can_spawn_processes(true);
+    config.set_can_inspect_processes(true);
 
     // Set correct command line arguments for the guest
     let wasi_args = args
@@ -95,7 +96,8 @@ pub(crate) async fn test() -> Result<()> {
 
     // Create wasmtime runtime
     let wasmtime_config = runtimes::wasmtime::default_config();
-    let runtime = runtimes::wasmtime::WasmtimeRuntime::new(&wasmtime_config)?;
+    let preemption = runtimes::wasmtime::Preemption::Fuel;
+    let runtime = runtimes::wasmtime::WasmtimeRuntime::new(&wasmtime_config, preemption)?;
 
     // Load and compile wasm module
     let path = args.value_of("wasm").unwrap();
@@ -201,9 +203,15 @@ pub(crate) async fn test() -> Result<()> {
         }
 
         let registry = Arc::new(DashMap::new());
-        let mut state =
-            DefaultProcessState::new(runtime.clone(), module.clone(), config.clone(), registry)
-                .unwrap();
+        let process_table = ProcessTable::new();
+        let mut state = DefaultProcessState::new(
+            runtime.clone(),
+            module.clone(),
+            config.clone(),
+            registry,
+            process_table,
+        )
+        .unwrap();
 
         // If --nocapture is not set, use in-memory stdout & stderr to hide output in case of
         // success
@@ -221,6 +229,7 @@ pub(crate) async fn test() -> Result<()> {
             &test_function.wasm_export_name,
             Vec::new(),
             None,
+            None,
         )
         .await
         .context(format!(
@@ -1,10 +1,14 @@
-use std::{env, fs, path::Path, sync::Arc};
+use std::{env, fs, path::Path, sync::Arc, time::Duration};
 
 use anyhow::{Context, Result};
 use clap::{crate_version, Arg, Command};
 
 use dashmap::DashMap;
-use lunatic_process::{runtimes, state::ProcessState};
+use lunatic_process::{
+    runtimes::{self, wasmtime::Preemption},
+    state::ProcessState,
+    table::ProcessTable,
+};
 use lunatic_process_api::ProcessConfigCtx;
 use lunatic_runtime::{spawn_wasm, DefaultProcessConfig, DefaultProcessState};
 
@@ -27,6 +31,15 @@ pub(crate) async fn execute() -> Result<()> {
                 .long("bench")
                 .help("Indicate that a benchmark is running"),
         )
+        .arg(
+            Arg::new("preemption")
+                .long("preemption")
+                .value_name("MODE")
+                .help("How long running processes are preempted")
+                .possible_values(["fuel", "epoch"])
+                .default_value("fuel")
+                .takes_value(true),
+        )
         .arg(
             Arg::new("wasm")
                 .value_name("WASM")
@@ -51,6 +64,7 @@ pub(crate) async fn execute() -> Result<()> {
     config.set_can_compile_modules(true);
     config.set_can_create_configs(true);
     config.set_can_spawn_processes(true);
+    config.set_can_inspect_processes(true);
 
     // Path to wasm file
     let path = args.value_of("wasm").unwrap();
@@ -82,8 +96,12 @@ pub(crate) async fn execute() -> Result<()> {
     }
 
     // Create wasmtime runtime
+    let preemption = match args.value_of("preemption") {
+        Some("epoch") => Preemption::Epoch(Duration::from_millis(10)),
+        _ => Preemption::Fuel,
+    };
     let wasmtime_config = runtimes::wasmtime::default_config();
-    let runtime = runtimes::wasmtime::WasmtimeRuntime::new(&wasmtime_config)?;
+    let runtime = runtimes::wasmtime::WasmtimeRuntime::new(&wasmtime_config, preemption)?;
 
     // Spawn main process
     let module = fs::read(path)?;
@@ -91,10 +109,16 @@ pub(crate) async fn execute() -> Result<()> {
     let module = runtime.compile_module::<DefaultProcessState>(module)?;
 
     let registry = Arc::new(DashMap::new());
-    let state =
-        DefaultProcessState::new(runtime.clone(), module.clone(), Arc::new(config), registry)
-            .unwrap();
-    let (task, _) = spawn_wasm(runtime, module, state, "_start", Vec::new(), None)
+    let process_table = ProcessTable::new();
+    let state = DefaultProcessState::new(
+        runtime.clone(),
+        module.clone(),
+        Arc::new(config),
+        registry,
+        process_table,
+    )
+    .unwrap();
+    let (task, _) = spawn_wasm(runtime, module, state, "_start", Vec::new(), None, None)
         .await
         .context(format!(
             "Failed to spawn process from {}::_start()",
@@ -12,9 +12,16 @@ use lunatic_networking_api::NetworkingCtx;
 use lunatic_process::config::ProcessConfig;
 use lunatic_process::runtimes::wasmtime::{WasmtimeCompiledModule, WasmtimeRuntime};
 use lunatic_process::state::{ConfigResources, ProcessState};
-use lunatic_process::{mailbox::MessageMailbox, message::Message, Process, Signal};
+use lunatic_process::table::ProcessTable;
+use lunatic_process::{
+    info::SharedProcessInfo,
+    mailbox::MessageMailbox,
+    message::{Message, SharedBuffer},
+    Process, Signal,
+};
 use lunatic_process_api::ProcessCtx;
 use lunatic_stdout_capture::StdoutCapture;
+use lunatic_timer_api::{TimerCtx, TimerResources};
 use lunatic_wasi_api::{build_wasi, LunaticWasiCtx};
 use uuid::Uuid;
 use wasmtime::{Linker, ResourceLimiter};
@@ -41,6 +48,8 @@ pub struct DefaultProcessState {
     signal_mailbox: (Sender<Signal>, Receiver<Signal>),
     // Messages sent to the process
     message_mailbox: MessageMailbox,
+    // Runtime information about the process, shared with all handles to it
+    process_info: SharedProcessInfo,
     // Resources
     resources: Resources,
     // WASI
@@ -51,8 +60,12 @@ pub struct DefaultProcessState {
     wasi_stderr: Option<StdoutCapture>,
     // Set to true if the WASM module has been instantiated
     initialized: bool,
+    // Set to true if a memory allocation was denied because of the memory limit
+    memory_limit_reached: bool,
     // Shared process registry
     registry: Arc<DashMap<String, Arc<dyn Process>>>,
+    // Shared table of all live processes
+    process_table: ProcessTable,
 }
 
 impl ProcessState for DefaultProcessState {
@@ -63,11 +76,17 @@ impl ProcessState for DefaultProcessState {
         module: WasmtimeCompiledModule<Self>,
         config: Arc<DefaultProcessConfig>,
         registry: Arc<DashMap<String, Arc<dyn Process>>>,
+        process_table: ProcessTable,
     ) -> Result<Self> {
         // TODO: Switch to new_v1() for distributed Lunatic to assure uniqueness across nodes.
         let id = Uuid::new_v4();
         let signal_mailbox = unbounded::<Signal>();
-        let message_mailbox = MessageMailbox::default();
+        let message_mailbox = MessageMailbox::new(
+            config.get_mailbox_capacity(),
+            config.get_mailbox_overflow_policy(),
+            Some(config.get_max_memory()),
+        );
+        let process_info = SharedProcessInfo::new(id, message_mailbox.clone());
         let state = Self {
             id,
             runtime: Some(runtime),
@@ -76,6 +95,7 @@ impl ProcessState for DefaultProcessState {
             message: None,
             signal_mailbox,
             message_mailbox,
+            process_info,
             resources: Resources::default(),
             wasi: build_wasi(
                 Some(config.command_line_arguments()),
@@ -85,7 +105,9 @@ impl ProcessState for DefaultProcessState {
             wasi_stdout: None,
             wasi_stderr: None,
             initialized: false,
+            memory_limit_reached: false,
             registry,
+            process_table,
         };
         Ok(state)
     }
@@ -98,6 +120,7 @@ impl ProcessState for DefaultProcessState {
         lunatic_version_api::register(linker)?;
         lunatic_wasi_api::register(linker)?;
         lunatic_registry_api::register(linker)?;
+        lunatic_timer_api::register(linker)?;
         Ok(())
     }
 
@@ -117,6 +140,14 @@ impl ProcessState for DefaultProcessState {
         &self.config
     }
 
+    fn memory_limit_reached(&self) -> bool {
+        self.memory_limit_reached
+    }
+
+    fn set_memory_limit_reached(&mut self) {
+        self.memory_limit_reached = true;
+    }
+
     fn module(&self) -> &WasmtimeCompiledModule<Self> {
         self.module.as_ref().unwrap()
     }
@@ -133,6 +164,10 @@ impl ProcessState for DefaultProcessState {
         &self.message_mailbox
     }
 
+    fn process_info(&self) -> &SharedProcessInfo {
+        &self.process_info
+    }
+
     fn config_resources(&self) -> &ConfigResources<<DefaultProcessState as ProcessState>::Config> {
         &self.resources.configs
     }
@@ -146,21 +181,28 @@ impl ProcessState for DefaultProcessState {
     fn registry(&self) -> &Arc<DashMap<String, Arc<dyn Process>>> {
         &self.registry
     }
+
+    fn process_table(&self) -> &ProcessTable {
+        &self.process_table
+    }
 }
 
 impl Default for DefaultProcessState {
     fn default() -> Self {
         let config = DefaultProcessConfig::default();
         let signal_mailbox = unbounded::<Signal>();
+        let id = Uuid::new_v4();
         let message_mailbox = MessageMailbox::default();
+        let process_info = SharedProcessInfo::new(id, message_mailbox.clone());
         Self {
-            id: Uuid::new_v4(),
+            id,
             runtime: None,
             module: None,
             config: Arc::new(config.clone()),
             message: None,
             signal_mailbox,
             message_mailbox,
+            process_info,
             resources: Resources::default(),
             wasi: build_wasi(
                 Some(config.command_line_arguments()),
@@ -171,7 +213,9 @@ impl Default for DefaultProcessState {
             wasi_stdout: None,
             wasi_stderr: None,
             initialized: false,
+            memory_limit_reached: false,
             registry: Arc::new(DashMap::new()),
+            process_table: ProcessTable::new(),
         }
     }
 }
@@ -187,7 +231,16 @@ impl Debug for DefaultProcessState {
 // Limit the maximum memory of the process depending on the environment it was spawned in.
 impl ResourceLimiter for DefaultProcessState {
     fn memory_growing(&mut self, _current: usize, desired: usize, _maximum: Option<usize>) -> bool {
-        desired <= self.config().get_max_memory()
+        // Message buffers held by the process count against the same limit as its linear memory.
+        let message_memory = self.message_mailbox.memory().used();
+        let allowed = desired + message_memory <= self.config().get_max_memory();
+        if allowed {
+            self.process_info.set_memory_size(desired);
+            self.message_mailbox.set_linear_memory(desired);
+        } else {
+            self.memory_limit_reached = true;
+        }
+        allowed
     }
 
     fn table_growing(&mut self, _current: u32, desired: u32, _maximum: Option<u32>) -> bool {
@@ -246,6 +299,22 @@ impl ProcessCtx<DefaultProcessState> for DefaultProcessState {
     fn process_resources_mut(&mut self) -> &mut lunatic_process_api::ProcessResources {
         &mut self.resources.processes
     }
+
+    fn message_resources(&self) -> &lunatic_process_api::MessageResources {
+        &self.resources.messages
+    }
+
+    fn message_resources_mut(&mut self) -> &mut lunatic_process_api::MessageResources {
+        &mut self.resources.messages
+    }
+
+    fn shared_buffer_resources(&self) -> &lunatic_process_api::SharedBufferResources {
+        &self.resources.shared_buffers
+    }
+
+    fn shared_buffer_resources_mut(&mut self) -> &mut lunatic_process_api::SharedBufferResources {
+        &mut self.resources.shared_buffers
+    }
 }
 
 impl NetworkingCtx for DefaultProcessState {
@@ -282,6 +351,12 @@ impl NetworkingCtx for DefaultProcessState {
     }
 }
 
+impl TimerCtx for DefaultProcessState {
+    fn timer_resources(&self) -> &TimerResources {
+        &self.resources.timers
+    }
+}
+
 impl LunaticWasiCtx for DefaultProcessState {
     fn wasi(&self) -> &WasiCtx {
         &self.wasi
@@ -317,11 +392,14 @@ pub(crate) struct Resources {
     pub(crate) configs: HashMapId<DefaultProcessConfig>,
     pub(crate) modules: HashMapId<WasmtimeCompiledModule<DefaultProcessState>>,
     pub(crate) processes: HashMapId<Arc<dyn Process>>,
+    pub(crate) messages: HashMapId<Message>,
+    pub(crate) shared_buffers: HashMapId<SharedBuffer>,
     pub(crate) dns_iterators: HashMapId<DnsIterator>,
-    pub(crate) tcp_listeners: HashMapId<TcpListener>,
+    pub(crate) tcp_listeners: HashMapId<Arc<TcpListener>>,
     pub(crate) tcp_streams: HashMapId<TcpStream>,
     pub(crate) udp_sockets: HashMapId<Arc<UdpSocket>>,
     pub(crate) errors: HashMapId<anyhow::Error>,
+    pub(crate) timers: TimerResources,
 }
 
 mod tests {
@@ -329,8 +407,9 @@ mod tests {
     async fn import_filter_signature_matches() {
         use crate::state::DefaultProcessState;
         use crate::DefaultProcessConfig;
-        use lunatic_process::runtimes::wasmtime::WasmtimeRuntime;
+        use lunatic_process::runtimes::wasmtime::{Preemption, WasmtimeRuntime};
         use lunatic_process::state::ProcessState;
+        use lunatic_process::table::ProcessTable;
         use lunatic_process::wasm::spawn_wasm;
         use std::sync::Arc;
 
@@ -340,17 +419,196 @@ mod tests {
         // Create wasmtime runtime
         let mut wasmtime_config = wasmtime::Config::new();
         wasmtime_config.async_support(true).consume_fuel(true);
-        let runtime = WasmtimeRuntime::new(&wasmtime_config).unwrap();
+        let runtime = WasmtimeRuntime::new(&wasmtime_config, Preemption::Fuel).unwrap();
 
         let raw_module = wat::parse_file("./wat/all_imports.wat").unwrap();
         let module = runtime.compile_module(raw_module).unwrap();
         let registry = Arc::new(dashmap::DashMap::new());
-        let state =
-            DefaultProcessState::new(runtime.clone(), module.clone(), Arc::new(config), registry)
-                .unwrap();
+        let process_table = ProcessTable::new();
+        let state = DefaultProcessState::new(
+            runtime.clone(),
+            module.clone(),
+            Arc::new(config),
+            registry,
+            process_table,
+        )
+        .unwrap();
+
+        spawn_wasm(runtime, module, state, "hello", Vec::new(), None, None)
+            .await
+            .unwrap();
+    }
+
+    #[async_std::test]
+    async fn resources_can_be_pushed_to_and_taken_from_messages() {
+        use crate::state::DefaultProcessState;
+        use crate::DefaultProcessConfig;
+        use lunatic_process::runtimes::wasmtime::{Preemption, WasmtimeRuntime};
+        use lunatic_process::state::ProcessState;
+        use lunatic_process::table::ProcessTable;
+        use lunatic_process::wasm::spawn_wasm;
+        use lunatic_process_api::ProcessConfigCtx;
+        use std::sync::Arc;
+
+        let mut config = DefaultProcessConfig::default();
+        config.set_can_compile_modules(true);
+        config.set_can_create_configs(true);
+
+        let mut wasmtime_config = wasmtime::Config::new();
+        wasmtime_config.async_support(true).consume_fuel(true);
+        let runtime = WasmtimeRuntime::new(&wasmtime_config, Preemption::Fuel).unwrap();
+
+        // Moves a config, a module and the same TCP listener twice through the scratch area and
+        // checks that all taken resources are usable. Any failure ends up in `unreachable`.
+        let raw_module = wat::parse_str(
+            r#"
+            (module
+                (import "lunatic::message" "create_data" (func $create_data (param i64 i64)))
+                (import "lunatic::message" "push_tcp_listener"
+                    (func $push_tcp_listener (param i64) (result i64)))
+                (import "lunatic::message" "take_tcp_listener"
+                    (func $take_tcp_listener (param i64) (result i64)))
+                (import "lunatic::message" "push_module"
+                    (func $push_module (param i64) (result i64)))
+                (import "lunatic::message" "take_module"
+                    (func $take_module (param i64) (result i64)))
+                (import "lunatic::message" "push_config"
+                    (func $push_config (param i64) (result i64)))
+                (import "lunatic::message" "take_config"
+                    (func $take_config (param i64) (result i64)))
+                (import "lunatic::networking" "tcp_bind"
+                    (func $tcp_bind (param i32 i32 i32 i32 i32 i32) (result i32)))
+                (import "lunatic::networking" "tcp_local_addr"
+                    (func $tcp_local_addr (param i64 i32) (result i32)))
+                (import "lunatic::process" "compile_module"
+                    (func $compile_module (param i32 i32 i32) (result i32)))
+                (import "lunatic::process" "drop_module" (func $drop_module (param i64)))
+                (import "lunatic::process" "create_config" (func $create_config (result i64)))
+                (import "lunatic::process" "config_set_max_memory"
+                    (func $config_set_max_memory (param i64 i64)))
+                (import "lunatic::process" "config_get_max_memory"
+                    (func $config_get_max_memory (param i64) (result i64)))
+
+                (memory (export "memory") 1)
+                ;; 127.0.0.1
+                (data (i32.const 0) "\7f\00\00\01")
+                ;; The smallest valid Wasm module
+                (data (i32.const 16) "\00asm\01\00\00\00")
+
+                (func (export "hello")
+                    (local $config i64)
+                    (local $module i64)
+                    (local $listener i64)
+                    (local $first i64)
+                    (local $second i64)
+
+                    (call $create_data (i64.const 0) (i64.const 0))
+
+                    ;; Config
+                    (local.set $config (call $create_config))
+                    (call $config_set_max_memory (local.get $config) (i64.const 65536))
+                    (local.set $config (call $take_config (call $push_config (local.get $config))))
+                    (if (i64.ne (call $config_get_max_memory (local.get $config)) (i64.const 65536))
+                        (then unreachable))
+
+                    ;; Module
+                    (if (call $compile_module (i32.const 16) (i32.const 8) (i32.const 32))
+                        (then unreachable))
+                    (local.set $module (i64.load (i32.const 32)))
+                    (local.set $module (call $take_module (call $push_module (local.get $module))))
+                    (call $drop_module (local.get $module))
+
+                    ;; TCP listener, pushed twice and still owned by this process
+                    (if (call $tcp_bind (i32.const 4) (i32.const 0) (i32.const 0) (i32.const 0)
+                            (i32.const 0) (i32.const 32))
+                        (then unreachable))
+                    (local.set $listener (i64.load (i32.const 32)))
+                    (local.set $first (call $push_tcp_listener (local.get $listener)))
+                    (local.set $second (call $push_tcp_listener (local.get $listener)))
+                    (local.set $first (call $take_tcp_listener (local.get $first)))
+                    (local.set $second (call $take_tcp_listener (local.get $second)))
+                    (if (call $tcp_local_addr (local.get $listener) (i32.const 32))
+                        (then unreachable))
+                    (if (call $tcp_local_addr (local.get $first) (i32.const 32))
+                        (then unreachable))
+                    (if (call $tcp_local_addr (local.get $second) (i32.const 32))
+                        (then unreachable))
+                )
+            )
+            "#,
+        )
+        .unwrap();
+        let module = runtime.compile_module(raw_module).unwrap();
+        let registry = Arc::new(dashmap::DashMap::new());
+        let state = DefaultProcessState::new(
+            runtime.clone(),
+            module.clone(),
+            Arc::new(config),
+            registry,
+            ProcessTable::new(),
+        )
+        .unwrap();
+
+        let (task, _) = spawn_wasm(runtime, module, state, "hello", Vec::new(), None, None)
+            .await
+            .unwrap();
+        assert!(task.await.is_ok());
+    }
 
-        spawn_wasm(runtime, module, state, "hello", Vec::new(), None)
+    #[async_std::test]
+    async fn epoch_preemption_interrupts_and_limits_processes() {
+        use crate::state::DefaultProcessState;
+        use crate::DefaultProcessConfig;
+        use lunatic_process::config::ProcessConfig;
+        use lunatic_process::runtimes::wasmtime::{default_config, Preemption, WasmtimeRuntime};
+        use lunatic_process::state::ProcessState;
+        use lunatic_process::table::ProcessTable;
+        use lunatic_process::wasm::spawn_wasm;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let preemption = Preemption::Epoch(Duration::from_millis(1));
+        let runtime = WasmtimeRuntime::new(&default_config(), preemption).unwrap();
+        let raw_module = wat::parse_str(
+            r#"
+            (module
+                (func (export "hello")
+                    (loop $forever (br $forever))
+                )
+            )
+            "#,
+        )
+        .unwrap();
+        let module = runtime.compile_module(raw_module).unwrap();
+
+        // The execution time limit can only be enforced if the endless loop yields on epoch
+        // changes. A fuel limit runs the process inside of the fuel engine.
+        let mut with_deadline = DefaultProcessConfig::default();
+        with_deadline.set_max_execution_time(Some(Duration::from_millis(50)));
+        let mut with_fuel = DefaultProcessConfig::default();
+        with_fuel.set_max_fuel(Some(1));
+
+        for config in [with_deadline, with_fuel] {
+            let state = DefaultProcessState::new(
+                runtime.clone(),
+                module.clone(),
+                Arc::new(config),
+                Arc::new(dashmap::DashMap::new()),
+                ProcessTable::new(),
+            )
+            .unwrap();
+            let (task, _) = spawn_wasm(
+                runtime.clone(),
+                module.clone(),
+                state,
+                "hello",
+                Vec::new(),
+                None,
+                None,
+            )
             .await
             .unwrap();
+            assert!(task.await.is_err());
+        }
     }
 }
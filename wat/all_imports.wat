@@ -10,6 +10,15 @@
     (import "lunatic::message" "read_data" (func (param i32 i32) (result i32)))
     (import "lunatic::message" "seek_data" (func (param i64)))
     (import "lunatic::message" "get_tag" (func (result i64)))
+    (import "lunatic::message" "set_priority" (func (param i32)))
+    (import "lunatic::message" "get_priority" (func (result i32)))
+    (import "lunatic::message" "set_ttl" (func (param i64 i64)))
+    (import "lunatic::message" "get_monitor_ref" (func (result i64)))
+    (import "lunatic::message" "get_process_down_id" (func (param i32)))
+    (import "lunatic::message" "exit_reason" (func (param i32) (result i32)))
+    (import "lunatic::message" "get_kill_reason" (func (result i64)))
+    (import "lunatic::message" "get_dead_letter_recipient" (func (param i32)))
+    (import "lunatic::message" "unwrap_dead_letter" (func (result i32)))
     (import "lunatic::message" "data_size" (func (result i64)))
     (import "lunatic::message" "push_process" (func (param i64) (result i64)))
     (import "lunatic::message" "take_process" (func (param i64) (result i64)))
@@ -17,9 +26,46 @@
     (import "lunatic::message" "take_tcp_stream" (func (param i64) (result i64)))
     (import "lunatic::message" "push_udp_socket" (func (param i64) (result i64)))
     (import "lunatic::message" "take_udp_socket" (func (param i64) (result i64)))
-    (import "lunatic::message" "send" (func (param i64)))
+    (import "lunatic::message" "push_tcp_listener" (func (param i64) (result i64)))
+    (import "lunatic::message" "take_tcp_listener" (func (param i64) (result i64)))
+    (import "lunatic::message" "push_module" (func (param i64) (result i64)))
+    (import "lunatic::message" "take_module" (func (param i64) (result i64)))
+    (import "lunatic::message" "push_config" (func (param i64) (result i64)))
+    (import "lunatic::message" "take_config" (func (param i64) (result i64)))
+    (import "lunatic::message" "create_shared_buffer" (func (param i32 i32) (result i64)))
+    (import "lunatic::message" "drop_shared_buffer" (func (param i64)))
+    (import "lunatic::message" "shared_buffer_size" (func (param i64) (result i64)))
+    (import "lunatic::message" "read_shared_buffer" (func (param i64 i64 i32 i32) (result i32)))
+    (import "lunatic::message" "push_shared_buffer" (func (param i64) (result i64)))
+    (import "lunatic::message" "take_shared_buffer" (func (param i64) (result i64)))
+    (import "lunatic::message" "create_data_handle" (func (param i64 i64) (result i64)))
+    (import "lunatic::message" "drop_handle" (func (param i64)))
+    (import "lunatic::message" "scratch_area_to_handle" (func (result i64)))
+    (import "lunatic::message" "handle_to_scratch_area" (func (param i64)))
+    (import "lunatic::message" "write_data_handle" (func (param i64 i32 i32) (result i32)))
+    (import "lunatic::message" "read_data_handle" (func (param i64 i32 i32) (result i32)))
+    (import "lunatic::message" "seek_data_handle" (func (param i64 i64)))
+    (import "lunatic::message" "get_tag_handle" (func (param i64) (result i64)))
+    (import "lunatic::message" "data_size_handle" (func (param i64) (result i64)))
+    (import "lunatic::message" "push_process_handle" (func (param i64 i64) (result i64)))
+    (import "lunatic::message" "take_process_handle" (func (param i64 i64) (result i64)))
+    (import "lunatic::message" "push_tcp_stream_handle" (func (param i64 i64) (result i64)))
+    (import "lunatic::message" "take_tcp_stream_handle" (func (param i64 i64) (result i64)))
+    (import "lunatic::message" "push_udp_socket_handle" (func (param i64 i64) (result i64)))
+    (import "lunatic::message" "take_udp_socket_handle" (func (param i64 i64) (result i64)))
+    (import "lunatic::message" "send_handle" (func (param i64 i64) (result i32)))
+    (import "lunatic::message" "send" (func (param i64) (result i32)))
+    (import "lunatic::message" "send_to_group" (func (param i32 i32) (result i64)))
     (import "lunatic::message" "send_receive_skip_search" (func (param i64 i32) (result i32)))
+    (import "lunatic::message" "make_ref" (func (result i64)))
+    (import "lunatic::message" "abandon_ref" (func (param i64)))
+    (import "lunatic::message" "dropped_replies" (func (result i64)))
+    (import "lunatic::message" "call" (func (param i64 i32) (result i32)))
     (import "lunatic::message" "receive" (func (param i32 i32 i32) (result i32)))
+    (import "lunatic::message" "receive_select" (func (param i32 i32 i32 i32) (result i32)))
+    (import "lunatic::message" "peek" (func (param i32 i32 i32 i32) (result i32)))
+    (import "lunatic::message" "receive_many" (func (param i32 i32 i32 i32) (result i32)))
+    (import "lunatic::message" "next_in_batch" (func (result i32)))
 
     (import "lunatic::networking" "resolve" (func (param i32 i32 i32 i32) (result i32)))
     (import "lunatic::networking" "drop_dns_iterator" (func (param i64)))
@@ -56,12 +102,24 @@
     (import "lunatic::process" "config_get_max_memory" (func (param i64) (result i64)))
     (import "lunatic::process" "config_set_max_fuel" (func (param i64 i64)))
     (import "lunatic::process" "config_get_max_fuel" (func (param i64) (result i64)))
+    (import "lunatic::process" "config_set_mailbox_capacity" (func (param i64 i64)))
+    (import "lunatic::process" "config_get_mailbox_capacity" (func (param i64) (result i64)))
+    (import "lunatic::process" "config_set_mailbox_overflow_policy" (func (param i64 i32)))
+    (import "lunatic::process" "config_get_mailbox_overflow_policy" (func (param i64) (result i32)))
+    (import "lunatic::process" "config_set_max_message_size" (func (param i64 i64)))
+    (import "lunatic::process" "config_get_max_message_size" (func (param i64) (result i64)))
+    (import "lunatic::process" "config_set_max_execution_time" (func (param i64 i64)))
+    (import "lunatic::process" "config_get_max_execution_time" (func (param i64) (result i64)))
+    (import "lunatic::process" "config_set_max_cpu_time" (func (param i64 i64)))
+    (import "lunatic::process" "config_get_max_cpu_time" (func (param i64) (result i64)))
     (import "lunatic::process" "config_can_compile_modules" (func (param i64) (result i32)))
     (import "lunatic::process" "config_set_can_compile_modules" (func (param i64 i32)))
     (import "lunatic::process" "config_can_create_configs" (func (param i64) (result i32)))
     (import "lunatic::process" "config_set_can_create_configs" (func (param i64 i32)))
     (import "lunatic::process" "config_can_spawn_processes" (func (param i64) (result i32)))
     (import "lunatic::process" "config_set_can_spawn_processes" (func (param i64 i32)))
+    (import "lunatic::process" "config_can_inspect_processes" (func (param i64) (result i32)))
+    (import "lunatic::process" "config_set_can_inspect_processes" (func (param i64 i32)))
     (import "lunatic::process" "spawn" (func (param i64 i64 i64 i32 i32 i32 i32 i32) (result i32)))
     (import "lunatic::process" "drop_process" (func (param i64)))
     (import "lunatic::process" "clone_process" (func (param i64) (result i64)))
@@ -69,8 +127,28 @@
     (import "lunatic::process" "die_when_link_dies" (func (param i32)))
     (import "lunatic::process" "this" (func (result i64)))
     (import "lunatic::process" "id" (func (param i64 i32)))
+    (import "lunatic::process" "info" (func (param i64 i32)))
+    (import "lunatic::process" "parent" (func (param i64 i32) (result i32)))
+    (import "lunatic::process" "children" (func (param i64 i32 i32) (result i64)))
     (import "lunatic::process" "link" (func (param i64 i64)))
+    (import "lunatic::process" "link_with_options" (func (param i64 i64 i32)))
     (import "lunatic::process" "unlink" (func (param i64)))
+    (import "lunatic::process" "monitor" (func (param i64) (result i64)))
+    (import "lunatic::process" "demonitor" (func (param i64 i64)))
+    (import "lunatic::process" "kill" (func (param i64 i64)))
+    (import "lunatic::process" "shutdown" (func (param i64 i64)))
+    (import "lunatic::process" "suspend" (func (param i64)))
+    (import "lunatic::process" "resume" (func (param i64)))
+    (import "lunatic::process" "list_processes" (func (param i32 i32) (result i64)))
+    (import "lunatic::process" "list_processes_by_module" (func (param i64 i32 i32) (result i64)))
+    (import "lunatic::process" "list_processes_by_parent" (func (param i32 i32 i32) (result i64)))
+    (import "lunatic::process" "lookup_process" (func (param i32 i32) (result i32)))
+    (import "lunatic::process" "set_dead_letter_sink" (func (param i64)))
+    (import "lunatic::process" "clear_dead_letter_sink" (func))
+    (import "lunatic::process" "dead_letter_stats" (func (param i32)))
+    (import "lunatic::process" "join_group" (func (param i32 i32 i64) (result i32)))
+    (import "lunatic::process" "leave_group" (func (param i32 i32 i64) (result i32)))
+    (import "lunatic::process" "group_members" (func (param i32 i32 i32 i32) (result i64)))
 
     (import "lunatic::version" "major" (func (result i32)))
     (import "lunatic::version" "minor" (func (result i32)))
@@ -84,5 +162,8 @@
     (import "lunatic::registry" "get" (func (param i32 i32 i32) (result i32)))
     (import "lunatic::registry" "remove" (func (param i32 i32)))
 
+    (import "lunatic::timer" "send_after" (func (param i64 i64) (result i64)))
+    (import "lunatic::timer" "cancel_timer" (func (param i64) (result i32)))
+
     (func (export "hello") nop)
 )
\ No newline at end of file